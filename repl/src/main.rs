@@ -4,6 +4,7 @@ extern crate rlisp;
 use std::env;
 
 const PROMPT: &'static str = "rlisp> ";
+const CONTINUATION_PROMPT: &'static str = "  ...> ";
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
@@ -13,10 +14,30 @@ fn main() {
     let mut rlisp = rlisp::Rlisp::new();
 
     loop {
-        let line = match readline::read(PROMPT) {
-            Some(line) => line, None => return
+        let prompt = if rlisp.is_pending() { CONTINUATION_PROMPT } else { PROMPT };
+
+        let line = match readline::read(prompt) {
+            Some(line) => line,
+            None       => {
+                print_results(rlisp.finish());
+                return;
+            },
         };
 
-        println!("{}", rlisp.execute(&line[..]));
+        print_results(rlisp.feed(&format!("{}\n", line)[..]));
+    }
+}
+
+fn print_results(results: Vec<Result<rlisp::Value, rlisp::RlispError>>) {
+    for result in results {
+        match result {
+            Ok(value) => println!("{}", value),
+            Err(e)    => {
+                println!("{}", e);
+                for frame in e.backtrace.iter() {
+                    println!("    at {}", frame);
+                }
+            },
+        }
     }
 }
\ No newline at end of file
@@ -7,6 +7,10 @@ use rlisp::Rlisp;
 
 use test::Bencher;
 
+const FIB: &'static str = "(def {fib n} {if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))})";
+const COUNT: &'static str = "(def {count n acc} {if (== n 0) acc {count (- n 1) (+ acc 1)}})";
+const PARTIAL: &'static str = "(def {step n acc} {if (== n 0) acc {(step (- n 1)) (+ acc 1)}})";
+
 #[bench]
 fn bench_rlisp_add(b: &mut Bencher) {
     let mut rlisp = Rlisp::new();
@@ -57,4 +61,45 @@ fn bench_rlisp_recursion_test_reverse(b: &mut Bencher) {
     b.iter(|| {
         test::black_box(rlisp.execute("(rev {1 2 3})"));
     });
-}
\ No newline at end of file
+}
+
+fn bench_program(b: &mut Bencher, compile: bool, def: &str, call: &str) {
+    let mut rlisp = Rlisp::new();
+    rlisp.set_compile(compile);
+
+    rlisp.execute(def);
+
+    b.iter(|| {
+        test::black_box(rlisp.execute(call));
+    });
+}
+
+#[bench]
+fn bench_rlisp_fib_evaluator(b: &mut Bencher) {
+    bench_program(b, false, FIB, "(fib 15)");
+}
+
+#[bench]
+fn bench_rlisp_fib_compiled(b: &mut Bencher) {
+    bench_program(b, true, FIB, "(fib 15)");
+}
+
+#[bench]
+fn bench_rlisp_count_evaluator(b: &mut Bencher) {
+    bench_program(b, false, COUNT, "(count 1000 0)");
+}
+
+#[bench]
+fn bench_rlisp_count_compiled(b: &mut Bencher) {
+    bench_program(b, true, COUNT, "(count 1000 0)");
+}
+
+#[bench]
+fn bench_rlisp_partial_evaluator(b: &mut Bencher) {
+    bench_program(b, false, PARTIAL, "(step 1000 0)");
+}
+
+#[bench]
+fn bench_rlisp_partial_compiled(b: &mut Bencher) {
+    bench_program(b, true, PARTIAL, "(step 1000 0)");
+}
@@ -1,6 +1,22 @@
 use cell::Cell::*;
-use cell::{Cell, LambdaSpec};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use globals;
+
+use cell::{Cell, ErrorSpec, FileHandle, FileSpec, Key, LambdaSpec, Tail};
+use compiler;
 use environment::Environment;
+use types::{self, Type};
+use super::{call_procedure, eval as eval_cell, expand, import as import_path, load as load_path};
 
 fn internal_error() -> ! {
     panic!("Internal type error");
@@ -98,7 +114,7 @@ pub fn div(_: Environment, args: &[Cell]) -> Cell {
         [Integer(_), Integer(0)] |
         [Integer(_), Float(0.0)] |
         [Float(_), Integer(0)]   |
-        [Float(_), Float(0.0)]   => Error("/, 2, can't divide by zero".to_string()),
+        [Float(_), Float(0.0)]   => Cell::error("/, 2, can't divide by zero".to_string()),
         [Integer(a), Integer(b)] => Integer(a / b),
         [Float(a),   Integer(b)] => Float(a / b as f64),
         [Integer(a), Float(b)]   => Float(a as f64 / b),
@@ -114,10 +130,10 @@ fn test_div() {
     assert_eq!(div(e.clone(), &[Integer(4), Float(2.0)]), Float(2.0));
     assert_eq!(div(e.clone(), &[Float(2.0), Integer(4)]), Float(0.5));
     assert_eq!(div(e.clone(), &[Float(2.0), Float(0.5)]), Float(4.0));
-    assert_eq!(div(e.clone(), &[Integer(8), Integer(0)]), Error("/, 2, can't divide by zero".to_string()));
-    assert_eq!(div(e.clone(), &[Integer(8), Float(0.0)]), Error("/, 2, can't divide by zero".to_string()));
-    assert_eq!(div(e.clone(), &[Float(2.0), Integer(0)]), Error("/, 2, can't divide by zero".to_string()));
-    assert_eq!(div(e.clone(), &[Float(2.0), Float(0.0)]), Error("/, 2, can't divide by zero".to_string()));
+    assert_eq!(div(e.clone(), &[Integer(8), Integer(0)]), Cell::error("/, 2, can't divide by zero".to_string()));
+    assert_eq!(div(e.clone(), &[Integer(8), Float(0.0)]), Cell::error("/, 2, can't divide by zero".to_string()));
+    assert_eq!(div(e.clone(), &[Float(2.0), Integer(0)]), Cell::error("/, 2, can't divide by zero".to_string()));
+    assert_eq!(div(e.clone(), &[Float(2.0), Float(0.0)]), Cell::error("/, 2, can't divide by zero".to_string()));
 }
 
 pub fn print(_: Environment, args: &[Cell]) -> Cell {
@@ -127,6 +143,173 @@ pub fn print(_: Environment, args: &[Cell]) -> Cell {
     Nil
 }
 
+pub fn open(_: Environment, args: &[Cell]) -> Cell {
+    let (path, mode) = match args {
+        [Str(ref path)]                => (path, "r"),
+        [Str(ref path), Str(ref mode)] => (path, &mode[..]),
+        _                              => internal_error(),
+    };
+
+    let handle = match mode {
+        "r" => fs::File::open(path).map(|f| FileHandle::Reader(BufReader::new(f))),
+        "w" => fs::File::create(path).map(FileHandle::Writer),
+        "a" => fs::OpenOptions::new().append(true).create(true).open(path).map(FileHandle::Writer),
+        _   => return Cell::error(format!("open, unknown mode \"{}\" expected \"r\", \"w\" or \"a\"", mode)),
+    };
+
+    match handle {
+        Ok(handle) => File(Rc::new(RefCell::new(FileSpec {
+            path: path.clone(),
+            handle: handle,
+        }))),
+        Err(e) => Cell::error(format!("open, {}: {}", path, e)),
+    }
+}
+
+fn not_readable(name: &str, handle: &FileHandle) -> Cell {
+    match *handle {
+        FileHandle::Closed => Cell::error(format!("{}, file is closed", name)),
+        _                  => Cell::error(format!("{}, file is not open for reading", name)),
+    }
+}
+
+fn not_writable(name: &str, handle: &FileHandle) -> Cell {
+    match *handle {
+        FileHandle::Closed => Cell::error(format!("{}, file is closed", name)),
+        _                  => Cell::error(format!("{}, file is not open for writing", name)),
+    }
+}
+
+pub fn read_line(_: Environment, args: &[Cell]) -> Cell {
+    let mut spec = match args {
+        [File(ref f)] => f.borrow_mut(),
+        _             => internal_error(),
+    };
+
+    if let FileHandle::Reader(ref mut reader) = spec.handle {
+        let mut line = String::new();
+        return match reader.read_line(&mut line) {
+            Ok(0)  => Nil,
+            Ok(_)  => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Str(line)
+            },
+            Err(e) => Cell::error(format!("read-line, {}", e)),
+        };
+    }
+
+    not_readable("read-line", &spec.handle)
+}
+
+pub fn read_all(_: Environment, args: &[Cell]) -> Cell {
+    let mut spec = match args {
+        [File(ref f)] => f.borrow_mut(),
+        _             => internal_error(),
+    };
+
+    if let FileHandle::Reader(ref mut reader) = spec.handle {
+        let mut content = String::new();
+        return match reader.read_to_string(&mut content) {
+            Ok(_)  => Str(content),
+            Err(e) => Cell::error(format!("read-all, {}", e)),
+        };
+    }
+
+    not_readable("read-all", &spec.handle)
+}
+
+pub fn write(_: Environment, args: &[Cell]) -> Cell {
+    let (mut spec, content) = match args {
+        [File(ref f), Str(ref s)] => (f.borrow_mut(), s),
+        _                         => internal_error(),
+    };
+
+    if let FileHandle::Writer(ref mut writer) = spec.handle {
+        return match writer.write_all(content.as_bytes()) {
+            Ok(_)  => Nil,
+            Err(e) => Cell::error(format!("write, {}", e)),
+        };
+    }
+
+    not_writable("write", &spec.handle)
+}
+
+pub fn close(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [File(ref f)] => f.borrow_mut().handle = FileHandle::Closed,
+        _             => internal_error(),
+    }
+    Nil
+}
+
+#[test]
+fn test_file_io() {
+    let e = Environment::new();
+    let path = Str(env::temp_dir().join("rlisp_test_file_io.txt").to_str().unwrap().to_string());
+
+    let f = open(e.clone(), &[path.clone(), Str("w".to_string())]);
+    assert_eq!(write(e.clone(), &[f.clone(), Str("åäö\r\nline 2".to_string())]), Nil);
+    assert_eq!(read_line(e.clone(), &[f.clone()]), Cell::error("read-line, file is not open for reading".to_string()));
+    assert_eq!(close(e.clone(), &[f.clone()]), Nil);
+    assert_eq!(write(e.clone(), &[f.clone(), Str("x".to_string())]), Cell::error("write, file is closed".to_string()));
+
+    let f = open(e.clone(), &[path.clone(), Str("a".to_string())]);
+    assert_eq!(write(e.clone(), &[f.clone(), Str("\n".to_string())]), Nil);
+    assert_eq!(close(e.clone(), &[f.clone()]), Nil);
+
+    let f = open(e.clone(), &[path.clone()]);
+    assert_eq!(read_line(e.clone(), &[f.clone()]), Str("åäö".to_string()));
+    assert_eq!(read_all(e.clone(), &[f.clone()]), Str("line 2\n".to_string()));
+    assert_eq!(read_line(e.clone(), &[f.clone()]), Nil);
+    assert_eq!(write(e.clone(), &[f.clone(), Str("x".to_string())]), Cell::error("write, file is not open for writing".to_string()));
+    assert_eq!(close(e.clone(), &[f.clone()]), Nil);
+
+    assert_eq!(open(e.clone(), &[path.clone(), Str("x".to_string())]),
+               Cell::error("open, unknown mode \"x\" expected \"r\", \"w\" or \"a\"".to_string()));
+    assert_eq!(open(e.clone(), &[Str("/nonexistent/rlisp".to_string())]).get_type(), Type::ErrorT);
+}
+
+pub fn slurp(_: Environment, args: &[Cell]) -> Cell {
+    let path = match args {
+        [Str(ref path)] => path,
+        _               => internal_error(),
+    };
+
+    let mut content = String::new();
+
+    match fs::File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+        Ok(_)  => Str(content),
+        Err(e) => Cell::error(format!("slurp, {}: {}", path, e)),
+    }
+}
+
+pub fn spit(_: Environment, args: &[Cell]) -> Cell {
+    let (path, content) = match args {
+        [Str(ref path), Str(ref content)] => (path, content),
+        _                                 => internal_error(),
+    };
+
+    match fs::File::create(path).and_then(|mut f| f.write_all(content.as_bytes())) {
+        Ok(_)  => Nil,
+        Err(e) => Cell::error(format!("spit, {}: {}", path, e)),
+    }
+}
+
+#[test]
+fn test_slurp_spit() {
+    let e = Environment::new();
+    let path = Str(env::temp_dir().join("rlisp_test_slurp_spit.txt").to_str().unwrap().to_string());
+
+    assert_eq!(spit(e.clone(), &[path.clone(), Str("ålder\n".to_string())]), Nil);
+    assert_eq!(slurp(e.clone(), &[path.clone()]), Str("ålder\n".to_string()));
+    assert_eq!(slurp(e.clone(), &[Str("/nonexistent/rlisp".to_string())]).get_type(), Type::ErrorT);
+}
+
 pub fn list(_: Environment, args: &[Cell]) -> Cell {
     Qexpr(args.to_vec())
 }
@@ -211,186 +394,1228 @@ fn test_len() {
     assert_eq!(len(e.clone(), &[Qexpr(vec![Integer(1), Integer(2), Integer(3)])]), Integer(3));
 }
 
-pub fn eval(env: Environment, args: &[Cell]) -> Cell {
+pub fn nth(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Qexpr(ref v)] => super::eval(env, &Sexpr(v.clone())),
-        [ref expr]     => super::eval(env, expr),
-        _              => internal_error(),
+        [Integer(i), Qexpr(ref v)] => {
+            if i < 0 || i as usize >= v.len() {
+                Cell::error(format!("nth, index {} out of range for list of length {}", i, v.len()))
+            } else {
+                v[i as usize].clone()
+            }
+        },
+        _ => internal_error(),
     }
 }
 
 #[test]
-fn test_eval() {
+fn test_nth() {
     let e = Environment::new();
-    let add_f = e.lookup(&"+".to_string());
-    assert_eq!(eval(e.clone(), &[Qexpr(vec![add_f, Integer(1), Integer(2), Integer(3)])]), Integer(6));
+    assert_eq!(nth(e.clone(), &[Integer(1), Qexpr(vec![Integer(1), Integer(2), Integer(3)])]), Integer(2));
+    assert_eq!(nth(e.clone(), &[Integer(3), Qexpr(vec![Integer(1), Integer(2), Integer(3)])]),
+               Cell::error("nth, index 3 out of range for list of length 3".to_string()));
+    assert_eq!(nth(e.clone(), &[Integer(-1), Qexpr(Vec::new())]),
+               Cell::error("nth, index -1 out of range for list of length 0".to_string()));
 }
 
-pub fn eq(_: Environment, args: &[Cell]) -> Cell {
+fn key(c: &Cell) -> Key {
+    match Key::from_cell(c) {
+        Some(k) => k,
+        None    => internal_error(),
+    }
+}
+
+pub fn get(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [ref a, ref b] => Bool(*a == *b),
-        _              => internal_error(),
+        [Map(ref m), ref k]          => m.get(&key(k)).cloned().unwrap_or(Nil),
+        [Map(ref m), ref k, ref def] => m.get(&key(k)).cloned().unwrap_or(def.clone()),
+        _                            => internal_error(),
     }
 }
 
 #[test]
-fn test_eq() {
+fn test_get() {
     let e = Environment::new();
-    let eq_f = e.lookup(&"==".to_string());
-    let ne_f = e.lookup(&"!=".to_string());
-    assert_eq!(eq(e.clone(), &[Integer(1), Integer(1)]), Bool(true));
-    assert_eq!(eq(e.clone(), &[Integer(1), Integer(2)]), Bool(false));
-    assert_eq!(eq(e.clone(), &[Integer(1), Float(1.0)]), Bool(false));
-    assert_eq!(eq(e.clone(), &[eq_f.clone(), eq_f.clone()]), Bool(true));
-    assert_eq!(eq(e.clone(), &[eq_f.clone(), ne_f.clone()]), Bool(false));
+    let mut m = BTreeMap::new();
+    m.insert(Key::Integer(1), Char('a'));
+    let m = Map(Box::new(m));
+
+    assert_eq!(get(e.clone(), &[m.clone(), Integer(1)]), Char('a'));
+    assert_eq!(get(e.clone(), &[m.clone(), Integer(2)]), Nil);
+    assert_eq!(get(e.clone(), &[m.clone(), Integer(2), Char('b')]), Char('b'));
 }
 
-pub fn ne(_: Environment, args: &[Cell]) -> Cell {
+pub fn assoc(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [ref a, ref b] => Bool(*a != *b),
-        _              => internal_error(),
+        [Map(ref m), rest..] => {
+            if rest.len() % 2 != 0 {
+                return Cell::error("assoc, requiers a value for every key".to_string());
+            }
+
+            let mut m = m.clone();
+
+            for (i, pair) in rest.chunks(2).enumerate() {
+                match Key::from_cell(&pair[0]) {
+                    Some(k) => { m.insert(k, pair[1].clone()); },
+                    None    => return Cell::error(format!("assoc, argument {} of type {} can't be used as a map key",
+                                                          2 * i + 2, pair[0].get_type())),
+                }
+            }
+
+            Map(m)
+        },
+        _ => internal_error(),
     }
 }
 
 #[test]
-fn test_ne() {
+fn test_assoc() {
     let e = Environment::new();
-    let eq_f = e.lookup(&"==".to_string());
-    let ne_f = e.lookup(&"!=".to_string());
-    assert_eq!(ne(e.clone(), &[Integer(1), Integer(1)]), Bool(false));
-    assert_eq!(ne(e.clone(), &[Integer(1), Integer(2)]), Bool(true));
-    assert_eq!(ne(e.clone(), &[Integer(1), Float(1.0)]), Bool(true));
-    assert_eq!(ne(e.clone(), &[eq_f.clone(), eq_f.clone()]), Bool(false));
-    assert_eq!(ne(e.clone(), &[eq_f.clone(), ne_f.clone()]), Bool(true));
+    let mut m = BTreeMap::new();
+    m.insert(Key::Integer(1), Char('a'));
+    m.insert(Key::Integer(2), Char('b'));
+
+    assert_eq!(assoc(e.clone(), &[Map(Box::new(BTreeMap::new())), Integer(1), Char('a'), Integer(2), Char('b')]), Map(Box::new(m)));
+    assert_eq!(assoc(e.clone(), &[Map(Box::new(BTreeMap::new())), Integer(1), Char('a'), Float(2.0), Char('b')]),
+               Cell::error("assoc, argument 4 of type Float can't be used as a map key".to_string()));
+    assert_eq!(assoc(e.clone(), &[Map(Box::new(BTreeMap::new())), Integer(1), Char('a'), Integer(2)]),
+               Cell::error("assoc, requiers a value for every key".to_string()));
 }
 
-pub fn lt(_: Environment, args: &[Cell]) -> Cell {
+pub fn dissoc(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Integer(ref a), Integer(ref b)] => Bool(*a < *b),
-        [Float(ref a), Float(ref b)]     => Bool(*a < *b),
-        [Char(ref a), Char(ref b)]       => Bool(*a < *b),
-        [Str(ref a), Str(ref b)]         => Bool(*a < *b),
-        _                                => Bool(false),
+        [Map(ref m), keys..] => {
+            let mut m = m.clone();
+            for k in keys.iter() {
+                m.remove(&key(k));
+            }
+            Map(m)
+        },
+        _ => internal_error(),
     }
 }
 
-pub fn lte(_: Environment, args: &[Cell]) -> Cell {
+#[test]
+fn test_dissoc() {
+    let e = Environment::new();
+    let mut m = BTreeMap::new();
+    m.insert(Key::Integer(1), Char('a'));
+    m.insert(Key::Integer(2), Char('b'));
+    let m = Map(Box::new(m));
+
+    assert_eq!(dissoc(e.clone(), &[m.clone(), Integer(1), Integer(2)]), Map(Box::new(BTreeMap::new())));
+    assert_eq!(dissoc(e.clone(), &[m.clone(), Integer(3)]), m);
+}
+
+pub fn keys(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Integer(ref a), Integer(ref b)] => Bool(*a <= *b),
-        [Float(ref a), Float(ref b)]     => Bool(*a <= *b),
-        [Char(ref a), Char(ref b)]       => Bool(*a <= *b),
-        [Str(ref a), Str(ref b)]         => Bool(*a <= *b),
-        _                                => Bool(false),
+        [Map(ref m)] => Qexpr(m.keys().map(|k| k.to_cell()).collect()),
+        _            => internal_error(),
     }
 }
 
-pub fn gt(_: Environment, args: &[Cell]) -> Cell {
+pub fn values(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Integer(ref a), Integer(ref b)] => Bool(*a > *b),
-        [Float(ref a), Float(ref b)]     => Bool(*a > *b),
-        [Char(ref a), Char(ref b)]       => Bool(*a > *b),
-        [Str(ref a), Str(ref b)]         => Bool(*a > *b),
-        _                                => Bool(false),
+        [Map(ref m)] => Qexpr(m.values().cloned().collect()),
+        _            => internal_error(),
     }
 }
 
-pub fn gte(_: Environment, args: &[Cell]) -> Cell {
+#[test]
+fn test_keys_values() {
+    let e = Environment::new();
+    let mut m = BTreeMap::new();
+    m.insert(Key::Str("b".to_string()), Integer(2));
+    m.insert(Key::Str("a".to_string()), Integer(1));
+    let m = Map(Box::new(m));
+
+    assert_eq!(keys(e.clone(), &[m.clone()]), Qexpr(vec![Str("a".to_string()), Str("b".to_string())]));
+    assert_eq!(values(e.clone(), &[m.clone()]), Qexpr(vec![Integer(1), Integer(2)]));
+}
+
+pub fn contains(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Integer(ref a), Integer(ref b)] => Bool(*a >= *b),
-        [Float(ref a), Float(ref b)]     => Bool(*a >= *b),
-        [Char(ref a), Char(ref b)]       => Bool(*a >= *b),
-        [Str(ref a), Str(ref b)]         => Bool(*a >= *b),
-        _                                => Bool(false),
+        [Map(ref m), ref k] => Bool(m.contains_key(&key(k))),
+        _                   => internal_error(),
     }
 }
 
-pub fn and(_: Environment, args: &[Cell]) -> Cell {
+#[test]
+fn test_contains() {
+    let e = Environment::new();
+    let mut m = BTreeMap::new();
+    m.insert(Key::Symbol("a".to_string()), Nil);
+    let m = Map(Box::new(m));
+
+    assert_eq!(contains(e.clone(), &[m.clone(), Symbol("a".to_string())]), Bool(true));
+    assert_eq!(contains(e.clone(), &[m.clone(), Str("a".to_string())]), Bool(false));
+}
+
+pub fn merge(_: Environment, args: &[Cell]) -> Cell {
+    let mut res = BTreeMap::new();
+
     for arg in args.iter() {
         match arg {
-            &Bool(a) => { if !a { return Bool(false); } }
-            _        => internal_error(),
+            &Map(ref m) => res.extend(m.iter().map(|(k, v)| (k.clone(), v.clone()))),
+            _           => internal_error(),
         }
     }
-    Bool(true)
+
+    Map(Box::new(res))
+}
+
+#[test]
+fn test_merge() {
+    let e = Environment::new();
+    let mut a = BTreeMap::new();
+    a.insert(Key::Integer(1), Char('a'));
+    a.insert(Key::Integer(2), Char('b'));
+    let mut b = BTreeMap::new();
+    b.insert(Key::Integer(2), Char('c'));
+    let mut res = a.clone();
+    res.insert(Key::Integer(2), Char('c'));
+
+    assert_eq!(merge(e.clone(), &[Map(Box::new(a)), Map(Box::new(b))]), Map(Box::new(res)));
+}
+
+fn char_count(s: &str) -> i64 {
+    s.chars().count() as i64
+}
+
+pub fn str_len(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s)] => Integer(char_count(s)),
+        _            => internal_error(),
+    }
+}
+
+#[test]
+fn test_str_len() {
+    let e = Environment::new();
+    assert_eq!(str_len(e.clone(), &[Str("hej".to_string())]), Integer(3));
+    assert_eq!(str_len(e.clone(), &[Str("åäö".to_string())]), Integer(3));
+}
+
+pub fn str_slice(_: Environment, args: &[Cell]) -> Cell {
+    let (s, start, end) = match args {
+        [Str(ref s), Integer(start)]               => (s, start, char_count(s)),
+        [Str(ref s), Integer(start), Integer(end)] => (s, start, end),
+        _                                          => internal_error(),
+    };
+
+    let len = char_count(s);
+
+    if start < 0 || start > len {
+        return Cell::error(format!("str-slice, index {} out of range for string of length {}", start, len));
+    }
+
+    if end < start || end > len {
+        return Cell::error(format!("str-slice, index {} out of range for string of length {}", end, len));
+    }
+
+    Str(s.chars().skip(start as usize).take((end - start) as usize).collect())
+}
+
+#[test]
+fn test_str_slice() {
+    let e = Environment::new();
+    assert_eq!(str_slice(e.clone(), &[Str("åäö!".to_string()), Integer(1), Integer(3)]), Str("äö".to_string()));
+    assert_eq!(str_slice(e.clone(), &[Str("åäö!".to_string()), Integer(2)]), Str("ö!".to_string()));
+    assert_eq!(str_slice(e.clone(), &[Str("åäö!".to_string()), Integer(2), Integer(5)]),
+               Cell::error("str-slice, index 5 out of range for string of length 4".to_string()));
 }
 
-pub fn or(_: Environment, args: &[Cell]) -> Cell {
+pub fn str_concat(_: Environment, args: &[Cell]) -> Cell {
+    let mut res = String::new();
+
     for arg in args.iter() {
         match arg {
-            &Bool(a) => { if a { return Bool(true); } }
-            _        => internal_error(),
+            &Str(ref s) => res.push_str(s),
+            _           => internal_error(),
         }
     }
-    Bool(false)
+
+    Str(res)
 }
 
-pub fn not(_: Environment, args: &[Cell]) -> Cell {
-    match args {
-        [Bool(cond)] => Bool(!cond),
-        _            => internal_error(),
-    }
+#[test]
+fn test_str_concat() {
+    let e = Environment::new();
+    assert_eq!(str_concat(e.clone(), &[Str("a".to_string()), Str("ö".to_string()), Str("c".to_string())]), Str("aöc".to_string()));
 }
 
-pub fn if_func(env: Environment, args: &[Cell]) -> Cell {
-     match args {
-        [Bool(cond), _, _] => {
-            if cond {
-                eval(env.clone(), &args[1..2])
-            } else {
-                eval(env.clone(), &args[2..3])
+pub fn str_split(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s), Str(ref sep)] => {
+            if sep.is_empty() {
+                return Cell::error("str-split, the separator can't be empty".to_string());
             }
+            Qexpr(s.split(&sep[..]).map(|p| Str(p.to_string())).collect())
         },
         _ => internal_error(),
     }
 }
 
-pub fn def(env: Environment, args: &[Cell]) -> Cell {
+#[test]
+fn test_str_split() {
+    let e = Environment::new();
+    assert_eq!(str_split(e.clone(), &[Str("a, b,".to_string()), Str(",".to_string())]),
+               Qexpr(vec![Str("a".to_string()), Str(" b".to_string()), Str("".to_string())]));
+}
+
+pub fn str_join(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Qexpr(ref v), ref b] => match (&v[..], b) {
-            ([Symbol(ref s)], b) => {
-                env.insert_top(s, b);
-                Nil
-            },
-            ([Symbol(ref s), args..], &Qexpr(ref body)) => {
-                let lambda = Lambda(Box::new(LambdaSpec {
-                    arguments:   args.to_vec(),
-                    body:        body.clone(),
-                    environment: env.clone(),
-                }));
-                env.insert_top(s, &lambda);
-                Nil
-            },
-            ([Symbol(_), ..], _) => {
-                Error("def, when defining a function argument 2 has to be a List".to_string())
+        [Str(ref sep), Qexpr(ref v)] => {
+            let mut res = String::new();
+            for (i, e) in v.iter().enumerate() {
+                if i != 0 {
+                    res.push_str(sep);
+                }
+                match e {
+                    &Str(ref s) => res.push_str(s),
+                    _           => internal_error(),
+                }
             }
-            _  => internal_error(),
+            Str(res)
         },
         _ => internal_error(),
     }
 }
 
-pub fn set(env: Environment, args: &[Cell]) -> Cell {
+#[test]
+fn test_str_join() {
+    let e = Environment::new();
+    assert_eq!(str_join(e.clone(), &[Str(", ".to_string()), Qexpr(vec![Str("a".to_string()), Str("b".to_string())])]),
+               Str("a, b".to_string()));
+    assert_eq!(str_join(e.clone(), &[Str(", ".to_string()), Qexpr(Vec::new())]), Str("".to_string()));
+}
+
+pub fn str_find(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Qexpr(ref v), ref b] => match &v[..] {
-            [Symbol(ref s)] => {
-                env.insert(s, b);
-                Nil
-            },
-            _  => internal_error(),
+        [Str(ref s), Str(ref needle)] => match s.find(&needle[..]) {
+            Some(i) => Integer(char_count(&s[..i])),
+            None    => Nil,
         },
         _ => internal_error(),
     }
 }
 
-pub fn lambda(env: Environment, args: &[Cell]) -> Cell {
+#[test]
+fn test_str_find() {
+    let e = Environment::new();
+    assert_eq!(str_find(e.clone(), &[Str("åäö".to_string()), Str("ö".to_string())]), Integer(2));
+    assert_eq!(str_find(e.clone(), &[Str("åäö".to_string()), Str("a".to_string())]), Nil);
+}
+
+pub fn str_replace(_: Environment, args: &[Cell]) -> Cell {
     match args {
-        [Qexpr(ref args), Qexpr(ref body)] => {
-            Lambda(Box::new(LambdaSpec {
-                arguments:   args.clone(),
-                body:        body.clone(),
-                environment: env,
-            }))
+        [Str(ref s), Str(ref from), Str(ref to)] => {
+            if from.is_empty() {
+                return Cell::error("str-replace, the pattern can't be empty".to_string());
+            }
+            Str(s.replace(&from[..], &to[..]))
         },
         _ => internal_error(),
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_str_replace() {
+    let e = Environment::new();
+    assert_eq!(str_replace(e.clone(), &[Str("aXbX".to_string()), Str("X".to_string()), Str("ö".to_string())]), Str("aöbö".to_string()));
+}
+
+pub fn str_trim(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s)] => Str(s.trim().to_string()),
+        _            => internal_error(),
+    }
+}
+
+pub fn str_upper(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s)] => Str(s.to_uppercase()),
+        _            => internal_error(),
+    }
+}
+
+pub fn str_lower(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s)] => Str(s.to_lowercase()),
+        _            => internal_error(),
+    }
+}
+
+#[test]
+fn test_str_trim_upper_lower() {
+    let e = Environment::new();
+    assert_eq!(str_trim(e.clone(), &[Str(" \tåb\n".to_string())]), Str("åb".to_string()));
+    assert_eq!(str_upper(e.clone(), &[Str("åb".to_string())]), Str("ÅB".to_string()));
+    assert_eq!(str_lower(e.clone(), &[Str("ÅB".to_string())]), Str("åb".to_string()));
+}
+
+pub fn starts_with(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s), Str(ref prefix)] => Bool(s.starts_with(&prefix[..])),
+        _                             => internal_error(),
+    }
+}
+
+pub fn ends_with(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s), Str(ref suffix)] => Bool(s.ends_with(&suffix[..])),
+        _                             => internal_error(),
+    }
+}
+
+#[test]
+fn test_starts_ends_with() {
+    let e = Environment::new();
+    assert_eq!(starts_with(e.clone(), &[Str("åbc".to_string()), Str("åb".to_string())]), Bool(true));
+    assert_eq!(ends_with(e.clone(), &[Str("åbc".to_string()), Str("b".to_string())]), Bool(false));
+}
+
+pub fn str_to_list(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref s)] => Qexpr(s.chars().map(Char).collect()),
+        _            => internal_error(),
+    }
+}
+
+pub fn list_to_str(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Qexpr(ref v)] => Str(v.iter().map(|c| match c {
+            &Char(c) => c,
+            _        => internal_error(),
+        }).collect()),
+        _ => internal_error(),
+    }
+}
+
+#[test]
+fn test_str_list_conversion() {
+    let e = Environment::new();
+    assert_eq!(str_to_list(e.clone(), &[Str("åb".to_string())]), Qexpr(vec![Char('å'), Char('b')]));
+    assert_eq!(list_to_str(e.clone(), &[Qexpr(vec![Char('å'), Char('b')])]), Str("åb".to_string()));
+}
+
+pub fn type_of(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref a] => Type(Box::new(a.get_type())),
+        _       => internal_error(),
+    }
+}
+
+#[test]
+fn test_type_of() {
+    use types::Type::*;
+
+    let e = Environment::new();
+    assert_eq!(type_of(e.clone(), &[Integer(1)]), Type(Box::new(IntegerT)));
+    assert_eq!(type_of(e.clone(), &[Qexpr(vec![Integer(1), Char('a')])]), Type(Box::new(RQexprT(vec![IntegerT, CharT]))));
+    assert_eq!(type_of(e.clone(), &[Type(Box::new(IntegerT))]), Type(Box::new(TypeT)));
+}
+
+pub fn is_type(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref a, Type(box ref t)] => Bool(a.is_type(t)),
+        _                        => internal_error(),
+    }
+}
+
+#[test]
+fn test_is_type() {
+    use types::Type::*;
+
+    let e = Environment::new();
+    let integers = Type(Box::new(RQexprT(vec![RElipsisT(Box::new(IntegerT))])));
+    assert_eq!(is_type(e.clone(), &[Integer(1), Type(Box::new(ROrT(Box::new(FloatT), Box::new(IntegerT))))]), Bool(true));
+    assert_eq!(is_type(e.clone(), &[Qexpr(vec![Integer(1), Integer(2)]), integers.clone()]), Bool(true));
+    assert_eq!(is_type(e.clone(), &[Qexpr(vec![Integer(1), Char('a')]), integers.clone()]), Bool(false));
+    assert_eq!(is_type(e.clone(), &[Qexpr(Vec::new()), integers.clone()]), Bool(true));
+}
+
+pub fn map(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref f, Qexpr(ref v)] => {
+            let mut res = Vec::with_capacity(v.len());
+
+            for e in v.iter() {
+                match call_procedure(env.clone(), f, vec![e.clone()]) {
+                    r @ Error(_) => return r,
+                    r            => res.push(r),
+                }
+            }
+
+            Qexpr(res)
+        },
+        _ => internal_error(),
+    }
+}
+
+#[test]
+fn test_map() {
+    let e = Environment::new();
+    let not_f = e.lookup("not");
+    assert_eq!(map(e.clone(), &[not_f, Qexpr(vec![Bool(true), Bool(false)])]), Qexpr(vec![Bool(false), Bool(true)]));
+}
+
+pub fn filter(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref f, Qexpr(ref v)] => {
+            let mut res = Vec::new();
+
+            for e in v.iter() {
+                match call_procedure(env.clone(), f, vec![e.clone()]) {
+                    Bool(true)   => res.push(e.clone()),
+                    Bool(false)  => (),
+                    r @ Error(_) => return r,
+                    r            => return Cell::error(format!("filter, predicate returned {} expected Bool", r.get_type())),
+                }
+            }
+
+            Qexpr(res)
+        },
+        _ => internal_error(),
+    }
+}
+
+#[test]
+fn test_filter() {
+    let e = Environment::new();
+    let not_f = e.lookup("not");
+    assert_eq!(filter(e.clone(), &[not_f.clone(), Qexpr(vec![Bool(true), Bool(false)])]), Qexpr(vec![Bool(false)]));
+
+    let list_f = e.lookup("list");
+    assert_eq!(filter(e.clone(), &[list_f, Qexpr(vec![Integer(1)])]),
+               Cell::error("filter, predicate returned {Integer} expected Bool".to_string()));
+}
+
+pub fn fold(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref f, ref init, Qexpr(ref v)] => {
+            let mut acc = init.clone();
+
+            for e in v.iter() {
+                acc = call_procedure(env.clone(), f, vec![acc, e.clone()]);
+
+                if let Error(_) = acc {
+                    return acc;
+                }
+            }
+
+            acc
+        },
+        _ => internal_error(),
+    }
+}
+
+#[test]
+fn test_fold() {
+    let e = Environment::new();
+    let sub_f = e.lookup("-");
+    assert_eq!(fold(e.clone(), &[sub_f, Integer(10), Qexpr(vec![Integer(1), Integer(2), Integer(3)])]), Integer(4));
+}
+
+pub fn eval(env: Environment, args: &[Cell]) -> Tail {
+    match args {
+        [Qexpr(ref v)] => Tail::Eval(env, Sexpr(v.clone(), None)),
+        [ref expr]     => Tail::Eval(env, expr.clone()),
+        _              => internal_error(),
+    }
+}
+
+#[test]
+fn test_eval() {
+    let e = Environment::new();
+    let add_f = e.lookup(&"+".to_string());
+    assert_eq!(super::force(eval(e.clone(), &[Qexpr(vec![add_f, Integer(1), Integer(2), Integer(3)])]), None), Integer(6));
+}
+
+pub fn eq(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref a, ref b] => Bool(*a == *b),
+        _              => internal_error(),
+    }
+}
+
+#[test]
+fn test_eq() {
+    let e = Environment::new();
+    let eq_f = e.lookup(&"==".to_string());
+    let ne_f = e.lookup(&"!=".to_string());
+    assert_eq!(eq(e.clone(), &[Integer(1), Integer(1)]), Bool(true));
+    assert_eq!(eq(e.clone(), &[Integer(1), Integer(2)]), Bool(false));
+    assert_eq!(eq(e.clone(), &[Integer(1), Float(1.0)]), Bool(false));
+    assert_eq!(eq(e.clone(), &[eq_f.clone(), eq_f.clone()]), Bool(true));
+    assert_eq!(eq(e.clone(), &[eq_f.clone(), ne_f.clone()]), Bool(false));
+}
+
+pub fn ne(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [ref a, ref b] => Bool(*a != *b),
+        _              => internal_error(),
+    }
+}
+
+#[test]
+fn test_ne() {
+    let e = Environment::new();
+    let eq_f = e.lookup(&"==".to_string());
+    let ne_f = e.lookup(&"!=".to_string());
+    assert_eq!(ne(e.clone(), &[Integer(1), Integer(1)]), Bool(false));
+    assert_eq!(ne(e.clone(), &[Integer(1), Integer(2)]), Bool(true));
+    assert_eq!(ne(e.clone(), &[Integer(1), Float(1.0)]), Bool(true));
+    assert_eq!(ne(e.clone(), &[eq_f.clone(), eq_f.clone()]), Bool(false));
+    assert_eq!(ne(e.clone(), &[eq_f.clone(), ne_f.clone()]), Bool(true));
+}
+
+pub fn lt(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Integer(ref a), Integer(ref b)] => Bool(*a < *b),
+        [Float(ref a), Float(ref b)]     => Bool(*a < *b),
+        [Char(ref a), Char(ref b)]       => Bool(*a < *b),
+        [Str(ref a), Str(ref b)]         => Bool(*a < *b),
+        _                                => Bool(false),
+    }
+}
+
+pub fn lte(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Integer(ref a), Integer(ref b)] => Bool(*a <= *b),
+        [Float(ref a), Float(ref b)]     => Bool(*a <= *b),
+        [Char(ref a), Char(ref b)]       => Bool(*a <= *b),
+        [Str(ref a), Str(ref b)]         => Bool(*a <= *b),
+        _                                => Bool(false),
+    }
+}
+
+pub fn gt(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Integer(ref a), Integer(ref b)] => Bool(*a > *b),
+        [Float(ref a), Float(ref b)]     => Bool(*a > *b),
+        [Char(ref a), Char(ref b)]       => Bool(*a > *b),
+        [Str(ref a), Str(ref b)]         => Bool(*a > *b),
+        _                                => Bool(false),
+    }
+}
+
+pub fn gte(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Integer(ref a), Integer(ref b)] => Bool(*a >= *b),
+        [Float(ref a), Float(ref b)]     => Bool(*a >= *b),
+        [Char(ref a), Char(ref b)]       => Bool(*a >= *b),
+        [Str(ref a), Str(ref b)]         => Bool(*a >= *b),
+        _                                => Bool(false),
+    }
+}
+
+fn condition(name: &str, env: Environment, form: &Cell) -> Result<bool, Cell> {
+    match eval_cell(env, form) {
+        Bool(b)      => Ok(b),
+        c @ Error(_) => Err(c),
+        c            => Err(Cell::error(format!("{}, condition is of type {} expected Bool", name, c.get_type()))),
+    }
+}
+
+fn sequence(env: Environment, forms: &[Cell]) -> Tail {
+    match forms {
+        [init.., ref last] => {
+            for form in init.iter() {
+                let c = eval_cell(env.clone(), form);
+
+                if let Error(_) = c {
+                    return Tail::Return(c);
+                }
+            }
+
+            Tail::Eval(env, last.clone())
+        },
+        [] => Tail::Return(Nil),
+    }
+}
+
+pub fn and(env: Environment, args: &[Cell]) -> Tail {
+    for arg in args.iter() {
+        match condition("and", env.clone(), arg) {
+            Ok(true)  => (),
+            Ok(false) => return Tail::Return(Bool(false)),
+            Err(e)    => return Tail::Return(e),
+        }
+    }
+    Tail::Return(Bool(true))
+}
+
+pub fn or(env: Environment, args: &[Cell]) -> Tail {
+    for arg in args.iter() {
+        match condition("or", env.clone(), arg) {
+            Ok(true)  => return Tail::Return(Bool(true)),
+            Ok(false) => (),
+            Err(e)    => return Tail::Return(e),
+        }
+    }
+    Tail::Return(Bool(false))
+}
+
+#[test]
+fn test_and_or() {
+    let e = Environment::new();
+    let undefined = Symbol("undefined".to_string());
+
+    assert_eq!(super::force(and(e.clone(), &[Bool(true), Bool(false), undefined.clone()]), None), Bool(false));
+    assert_eq!(super::force(or(e.clone(), &[Bool(false), Bool(true), undefined.clone()]), None), Bool(true));
+    assert_eq!(super::force(and(e.clone(), &[Bool(true), Integer(1)]), None),
+               Cell::error("and, condition is of type Integer expected Bool".to_string()));
+    assert_eq!(super::force(or(e.clone(), &[Bool(false), undefined.clone()]), None),
+               Cell::error("Undefined symbol: undefined".to_string()));
+}
+
+pub fn not(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Bool(cond)] => Bool(!cond),
+        _            => internal_error(),
+    }
+}
+
+pub fn if_func(env: Environment, args: &[Cell]) -> Tail {
+    match args {
+        [ref test, ref then, ref otherwise] => match condition("if", env.clone(), test) {
+            Ok(cond) => {
+                let branch = if cond { then } else { otherwise };
+
+                match branch {
+                    &Qexpr(ref v) => Tail::Eval(env, Sexpr(v.clone(), None)),
+                    _             => Tail::Eval(env, branch.clone()),
+                }
+            },
+            Err(e) => Tail::Return(e),
+        },
+        _ => internal_error(),
+    }
+}
+
+#[test]
+fn test_if() {
+    let e = Environment::new();
+    let undefined = Symbol("undefined".to_string());
+
+    assert_eq!(super::force(if_func(e.clone(), &[Bool(true), Integer(1), undefined.clone()]), None), Integer(1));
+    assert_eq!(super::force(if_func(e.clone(), &[Bool(false), undefined.clone(), Qexpr(vec![Symbol("+".to_string()), Integer(1), Integer(2)])]), None), Integer(3));
+    assert_eq!(super::force(if_func(e.clone(), &[Integer(1), Integer(1), Integer(2)]), None),
+               Cell::error("if, condition is of type Integer expected Bool".to_string()));
+}
+
+pub fn when(env: Environment, args: &[Cell]) -> Tail {
+    match args {
+        [ref test, body..] => match condition("when", env.clone(), test) {
+            Ok(true)  => sequence(env, body),
+            Ok(false) => Tail::Return(Nil),
+            Err(e)    => Tail::Return(e),
+        },
+        _ => internal_error(),
+    }
+}
+
+pub fn unless(env: Environment, args: &[Cell]) -> Tail {
+    match args {
+        [ref test, body..] => match condition("unless", env.clone(), test) {
+            Ok(true)  => Tail::Return(Nil),
+            Ok(false) => sequence(env, body),
+            Err(e)    => Tail::Return(e),
+        },
+        _ => internal_error(),
+    }
+}
+
+pub fn cond(env: Environment, args: &[Cell]) -> Tail {
+    for clause in args.iter() {
+        match clause {
+            &Qexpr(ref v) => match &v[..] {
+                [ref test, body..] => match condition("cond", env.clone(), test) {
+                    Ok(true) if body.is_empty() => return Tail::Return(Bool(true)),
+                    Ok(true)                    => return sequence(env, body),
+                    Ok(false)                   => (),
+                    Err(e)                      => return Tail::Return(e),
+                },
+                _ => internal_error(),
+            },
+            _ => internal_error(),
+        }
+    }
+    Tail::Return(Nil)
+}
+
+#[test]
+fn test_cond() {
+    let e = Environment::new();
+    let clause = |test, value| Qexpr(vec![Bool(test), Integer(value)]);
+
+    assert_eq!(super::force(cond(e.clone(), &[clause(false, 1), clause(true, 2), clause(true, 3)]), None), Integer(2));
+    assert_eq!(super::force(cond(e.clone(), &[clause(false, 1)]), None), Nil);
+}
+
+pub fn begin(env: Environment, args: &[Cell]) -> Tail {
+    sequence(env, args)
+}
+
+#[test]
+fn test_begin() {
+    let e = Environment::new();
+    assert_eq!(super::force(begin(e.clone(), &[Integer(1), Integer(2)]), None), Integer(2));
+    assert_eq!(super::force(begin(e.clone(), &[]), None), Nil);
+    assert_eq!(super::force(begin(e.clone(), &[Symbol("undefined".to_string()), Integer(2)]), None),
+               Cell::error("Undefined symbol: undefined".to_string()));
+}
+
+fn let_bindings(env: Environment, args: &[Cell], sequential: bool) -> Tail {
+    let (bindings, body) = match args {
+        [Qexpr(ref bindings), body..] => (bindings, body),
+        _                             => internal_error(),
+    };
+
+    let sub_env = env.make_sub_environment();
+    let mut values = Vec::new();
+
+    for binding in bindings.iter() {
+        let (s, form) = match binding {
+            &Qexpr(ref b) => match &b[..] {
+                [Symbol(ref s), ref form] => (s, form),
+                _                         => internal_error(),
+            },
+            _ => internal_error(),
+        };
+
+        let value = eval_cell(if sequential { sub_env.clone() } else { env.clone() }, form);
+
+        if let Error(_) = value {
+            return Tail::Return(value);
+        }
+
+        if sequential {
+            sub_env.insert(s, &value);
+        } else {
+            values.push((s, value));
+        }
+    }
+
+    for (s, value) in values.into_iter() {
+        sub_env.insert(s, &value);
+    }
+
+    sequence(sub_env, body)
+}
+
+pub fn let_func(env: Environment, args: &[Cell]) -> Tail {
+    let_bindings(env, args, false)
+}
+
+pub fn let_star(env: Environment, args: &[Cell]) -> Tail {
+    let_bindings(env, args, true)
+}
+
+#[test]
+fn test_let() {
+    let e = Environment::new();
+    e.insert("a", &Integer(10));
+
+    let binding = |s: &str, form| Qexpr(vec![Symbol(s.to_string()), form]);
+    let bindings = Qexpr(vec![binding("a", Integer(1)), binding("b", Symbol("a".to_string()))]);
+
+    assert_eq!(super::force(let_func(e.clone(), &[bindings.clone(), Symbol("b".to_string())]), None), Integer(10));
+    assert_eq!(super::force(let_star(e.clone(), &[bindings.clone(), Symbol("b".to_string())]), None), Integer(1));
+    assert_eq!(e.lookup("b"), Cell::error("Undefined symbol: b".to_string()));
+}
+
+pub fn error(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref message)]              => Cell::error(message.clone()),
+        [Str(ref message), ref payload] => Cell::error_with_payload(message.clone(), payload.clone()),
+        _                               => internal_error(),
+    }
+}
+
+pub fn raise(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref message)] => Cell::error_with_payload(message.clone(), args[0].clone()),
+        [ref payload]      => Cell::error_with_payload(format!("{}", payload), payload.clone()),
+        _                  => internal_error(),
+    }
+}
+
+#[test]
+fn test_error_raise() {
+    let e = Environment::new();
+
+    match error(e.clone(), &[Str("bad".to_string()), Integer(1)]) {
+        Error(box spec) => {
+            assert_eq!(spec.message, "bad");
+            assert_eq!(spec.payload, Integer(1));
+        },
+        c => panic!("{} is not an error", c),
+    }
+
+    assert_eq!(error(e.clone(), &[Str("bad".to_string())]), Cell::error("bad".to_string()));
+    assert_eq!(raise(e.clone(), &[Qexpr(vec![Integer(1)])]), Cell::error("{1}".to_string()));
+}
+
+pub fn try_func(env: Environment, args: &[Cell]) -> Tail {
+    let (body, handler) = match args {
+        [ref body, Sexpr(ref handler, _)] => (body, handler),
+        _                                 => internal_error(),
+    };
+
+    let (name, handler) = match &handler[..] {
+        [Symbol(ref catch), Symbol(ref name), handler..] if &catch[..] == "catch" => (name, handler),
+        _ => return Tail::Return(Cell::error("try, expected (catch name handler...) as argument 2".to_string())),
+    };
+
+    let c = eval_cell(env.clone(), body);
+
+    if let Error(_) = c {
+        if !c.is_escape() {
+            let sub_env = env.make_sub_environment();
+            sub_env.insert(name, &c);
+            return sequence(sub_env, handler);
+        }
+    }
+
+    Tail::Return(c)
+}
+
+#[test]
+fn test_try() {
+    let e = Environment::new();
+    let sym = |s: &str| Symbol(s.to_string());
+    let handler = Sexpr(vec![sym("catch"), sym("e"), Integer(2)], None);
+
+    assert_eq!(super::force(try_func(e.clone(), &[sym("undefined"), handler.clone()]), None), Integer(2));
+    assert_eq!(super::force(try_func(e.clone(), &[Integer(1), handler.clone()]), None), Integer(1));
+    assert_eq!(super::force(try_func(e.clone(), &[Integer(1), Sexpr(vec![sym("e"), Integer(2)], None)]), None),
+               Cell::error("try, expected (catch name handler...) as argument 2".to_string()));
+}
+
+pub fn is_error(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Error(_)] => Bool(true),
+        [_]        => Bool(false),
+        _          => internal_error(),
+    }
+}
+
+pub fn error_message(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Error(box ErrorSpec { ref message, .. })] => Str(message.clone()),
+        _                                          => internal_error(),
+    }
+}
+
+pub fn payload(_: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Error(box ErrorSpec { ref payload, .. })] => payload.clone(),
+        _                                          => internal_error(),
+    }
+}
+
+#[test]
+fn test_error_inspection() {
+    let e = Environment::new();
+    let err = Cell::error_with_payload("bad".to_string(), Integer(7));
+
+    assert_eq!(is_error(e.clone(), &[err.clone()]), Bool(true));
+    assert_eq!(is_error(e.clone(), &[Integer(1)]), Bool(false));
+    assert_eq!(error_message(e.clone(), &[err.clone()]), Str("bad".to_string()));
+    assert_eq!(payload(e.clone(), &[err.clone()]), Integer(7));
+    assert_eq!(types::validate(globals::GLOBAL_ENVIROMENT.get("payload").unwrap(), &[Integer(1)]),
+               Some("payload, argument 1 is of type Integer expected Error".to_string()));
+    assert!(globals::GLOBAL_ENVIROMENT.get("error?").unwrap().accepts_errors);
+    assert!(!globals::GLOBAL_ENVIROMENT.get("head").unwrap().accepts_errors);
+}
+
+pub fn def(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Qexpr(ref v), ref b] => match (&v[..], b) {
+            ([Symbol(ref s)], b) => {
+                env.insert_top(s, &named(b, s));
+                Nil
+            },
+            ([Symbol(ref s), args..], &Qexpr(ref body)) => {
+                let (arguments, argument_types) = match lambda_arguments(args) {
+                    Ok(arguments) => arguments,
+                    Err(e)        => return Cell::error(format!("def, {}", e)),
+                };
+
+                let lambda = Lambda(Box::new(LambdaSpec {
+                    name:           Some(Rc::new(s.clone())),
+                    code:           compiler::compile(&env, &arguments, body),
+                    arguments:      arguments,
+                    argument_types: argument_types,
+                    body:           body.clone(),
+                    environment:    env.clone(),
+                }));
+                env.insert_top(s, &lambda);
+                Nil
+            },
+            ([Symbol(_), ..], _) => {
+                Cell::error("def, when defining a function argument 2 has to be a List".to_string())
+            }
+            _  => internal_error(),
+        },
+        _ => internal_error(),
+    }
+}
+
+pub fn defmacro(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Qexpr(ref v), Qexpr(ref body)] => match &v[..] {
+            [Symbol(ref s), args..] => {
+                let (arguments, argument_types) = match lambda_arguments(args) {
+                    Ok(arguments) => arguments,
+                    Err(e)        => return Cell::error(format!("defmacro, {}", e)),
+                };
+
+                let m = Macro(Box::new(LambdaSpec {
+                    name:           Some(Rc::new(s.clone())),
+                    arguments:      arguments,
+                    argument_types: argument_types,
+                    body:           body.clone(),
+                    environment:    env.clone(),
+                    code:           None,
+                }));
+                env.insert_top(s, &m);
+                Nil
+            },
+            _ => internal_error(),
+        },
+        _ => internal_error(),
+    }
+}
+
+pub fn macroexpand(env: Environment, args: &[Cell]) -> Cell {
+    let mut form = match args {
+        [Qexpr(ref v)] => Sexpr(v.clone(), None),
+        [ref c]        => c.clone(),
+        _              => internal_error(),
+    };
+
+    loop {
+        let expanded = match form {
+            // Only a symbol naming a macro is resolved, any other head could run code
+            Sexpr(ref v, ref location) if !v.is_empty() => match v[0] {
+                Symbol(ref s) => match env.lookup(s) {
+                    Macro(box ref spec) => expand(spec, &v[1..], location),
+                    _                   => break,
+                },
+                _ => break,
+            },
+            _ => break,
+        };
+
+        if let Error(_) = expanded {
+            return expanded;
+        }
+
+        form = expanded;
+    }
+
+    match form {
+        Sexpr(v, _) => Qexpr(v),
+        c           => c,
+    }
+}
+
+fn named(c: &Cell, name: &str) -> Cell {
+    match c {
+        &Lambda(box ref lambda) if lambda.name.is_none() => {
+            let mut lambda = lambda.clone();
+            lambda.name = Some(Rc::new(name.to_string()));
+            Lambda(Box::new(lambda))
+        },
+        _ => c.clone(),
+    }
+}
+
+pub fn set(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Qexpr(ref v), ref b] => match &v[..] {
+            [Symbol(ref s)] => {
+                env.insert(s, b);
+                Nil
+            },
+            _  => internal_error(),
+        },
+        _ => internal_error(),
+    }
+}
+
+pub fn lambda(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Qexpr(ref args), Qexpr(ref body)] => {
+            let (arguments, argument_types) = match lambda_arguments(args) {
+                Ok(arguments) => arguments,
+                Err(e)        => return Cell::error(format!("lambda, {}", e)),
+            };
+
+            Lambda(Box::new(LambdaSpec {
+                name:           None,
+                code:           compiler::compile(&env, &arguments, body),
+                arguments:      arguments,
+                argument_types: argument_types,
+                body:           body.clone(),
+                environment:    env,
+            }))
+        },
+        _ => internal_error(),
+    }
+}
+
+fn lambda_arguments(args: &[Cell]) -> Result<(Vec<Cell>, Vec<Type>), String> {
+    let mut arguments = Vec::new();
+    let mut argument_types = Vec::new();
+    let mut annotated = false;
+
+    for arg in args.iter() {
+        match arg {
+            &Symbol(ref s) => {
+                arguments.push(arg.clone());
+                argument_types.push(types::default_argument_type(s));
+                annotated = false;
+            },
+            &Type(box ref t) => {
+                if annotated || arguments.is_empty() {
+                    return Err(format!("type annotation :{} has to follow an argument", t));
+                }
+
+                let last = argument_types.len() - 1;
+                argument_types[last] = match arguments[last] {
+                    Symbol(ref s) if &s[..] == "..." && !t.is_elipsis() => Type::RElipsisT(Box::new(t.clone())),
+                    _                                                   => t.clone(),
+                };
+                annotated = true;
+            },
+            _ => internal_error(),
+        }
+    }
+
+    let mut found_optional = false;
+
+    for (i, t) in argument_types.iter().enumerate() {
+        if t.is_elipsis() && i != argument_types.len() - 1 {
+            return Err(format!("{} has to be the last argument", arguments[i]));
+        }
+
+        if t.is_optional() {
+            found_optional = true;
+        } else if found_optional && !t.is_elipsis() {
+            return Err(format!("{} can't follow an optional argument", arguments[i]));
+        }
+    }
+
+    Ok((arguments, argument_types))
+}
+
+#[test]
+fn test_lambda_arguments() {
+    use types::Type::*;
+
+    let x = Symbol("x".to_string());
+    let rest = Symbol("...".to_string());
+
+    assert_eq!(lambda_arguments(&[x.clone(), Type(Box::new(IntegerT)), rest.clone()]),
+               Ok((vec![x.clone(), rest.clone()], vec![IntegerT, RElipsisT(Box::new(AnyT))])));
+    assert_eq!(lambda_arguments(&[rest.clone(), Type(Box::new(IntegerT))]),
+               Ok((vec![rest.clone()], vec![RElipsisT(Box::new(IntegerT))])));
+    assert_eq!(lambda_arguments(&[Type(Box::new(IntegerT)), x.clone()]),
+               Err("type annotation :Integer has to follow an argument".to_string()));
+    assert_eq!(lambda_arguments(&[rest.clone(), x.clone()]),
+               Err("... has to be the last argument".to_string()));
+    assert_eq!(lambda_arguments(&[x.clone(), Type(Box::new(ROptionalT(Box::new(IntegerT)))), Symbol("y".to_string())]),
+               Err("y can't follow an optional argument".to_string()));
+}
+
+pub fn module(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Qexpr(ref name), Qexpr(ref exports)] => {
+            let name = match &name[..] {
+                [Symbol(ref name)] => name,
+                _                  => internal_error(),
+            };
+
+            let exports = exports.iter().map(|e| match e {
+                &Symbol(ref s) => s.clone(),
+                _              => internal_error(),
+            }).collect();
+
+            if env.declare_module(name, exports) {
+                Nil
+            } else {
+                Cell::error("module, can only be declared in an imported file".to_string())
+            }
+        },
+        _ => internal_error(),
+    }
+}
+
+pub fn import(env: Environment, args: &[Cell]) -> Cell {
+    let (path, names) = match args {
+        [Str(ref path)]                  => (path, None),
+        [Str(ref path), Qexpr(ref names)] => (path, Some(names)),
+        _                                => internal_error(),
+    };
+
+    let (name, module) = match import_path(env.clone(), Path::new(path)) {
+        Ok(module) => module,
+        Err(e)     => return e,
+    };
+
+    if let Some(names) = names {
+        let mut imported = Vec::new();
+
+        for n in names.iter() {
+            let n = match n {
+                &Symbol(ref n) => n,
+                _              => internal_error(),
+            };
+
+            match module {
+                Map(ref m) => match m.get(&Key::Symbol(n.clone())) {
+                    Some(c) => imported.push((n, c.clone())),
+                    None    => return Cell::error(format!("import, {} does not export {}", name, n)),
+                },
+                _ => internal_error(),
+            }
+        }
+
+        for (n, c) in imported.into_iter() {
+            env.insert_top(n, &c);
+        }
+    }
+
+    env.insert_import(&name, &module);
+
+    Nil
+}
+
+pub fn load(env: Environment, args: &[Cell]) -> Cell {
+    match args {
+        [Str(ref path)] => match load_path(env, Path::new(path)) {
+            Ok(c)  => c,
+            Err(e) => Cell::from_error(e),
+        },
+        _ => internal_error(),
+    }
+}
+
+// Continuations can only escape, they stop working once call/cc has returned
+pub fn call_cc(env: Environment, args: &[Cell]) -> Cell {
+    let context = env.context();
+    let id = context.enter_continuation();
+    let mut result = call_procedure(env, &args[0], vec![Continuation(id)]);
+    context.leave_continuation();
+
+    if let Error(ref mut e) = result {
+        if e.escape == Some(id) {
+            return mem::replace(&mut e.payload, Nil);
+        }
+    }
+
+    result
+}
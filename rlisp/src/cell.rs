@@ -1,21 +1,55 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::fs;
+use std::io::BufReader;
+use std::rc::Rc;
 
 #[cfg(test)]
 use std::mem;
 
+use compiler::Chunk;
 use environment::Environment;
+use error::{Location, RlispError};
+use parser;
 use types::Type::*;
-use types::Type;
+use types::{self, Type};
 
 use self::Cell::*;
 
 pub type BuiltinFunction = fn (env: Environment, args: &[Cell]) -> Cell;
+pub type TailBuiltinFunction = fn (env: Environment, args: &[Cell]) -> Tail;
+pub type NativeFunction = Box<Fn(&[Cell]) -> Cell>;
+
+pub enum Function {
+    Func(BuiltinFunction),
+    TailFunc(TailBuiltinFunction),
+    Special(TailBuiltinFunction),
+}
+
+pub enum Tail {
+    Return(Cell),
+    Eval(Environment, Cell),
+    Call(Option<Rc<String>>, Environment, Cell),
+    Run(Option<Rc<String>>, Environment, Rc<Chunk>),
+}
 
 pub struct BuiltinFunctionSpec {
-    pub func: BuiltinFunction,
+    pub func: Function,
     pub name: &'static str,
     pub argument_types: &'static [Type],
+    // Errors in the arguments are passed on instead of returned, escapes still stop the call
+    pub accepts_errors: bool,
+}
+
+impl BuiltinFunctionSpec {
+    pub fn is_special(&self) -> bool {
+        match self.func {
+            Function::Special(_) => true,
+            _                    => false,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -24,11 +58,90 @@ pub struct CurriedBuiltinSpec {
     pub bound_args: Vec<Cell>,
 }
 
+// A builtin registered by the host at runtime
+pub struct NativeFunctionSpec {
+    pub func: NativeFunction,
+    pub name: Rc<String>,
+    pub argument_types: Vec<Type>,
+}
+
+#[derive(Clone)]
+pub struct NativeSpec {
+    pub function: Rc<NativeFunctionSpec>,
+    pub bound_args: Vec<Cell>,
+}
+
+pub type Span = Option<Rc<Location>>;
+
+// Continuation ids start at 1, so no call/cc ever stops a limit error
+const LIMIT_ESCAPE: usize = 0;
+
+#[derive(Clone)]
+pub struct FrameSpec {
+    pub name: Option<Rc<String>>,
+    pub location: Span,
+}
+
+#[derive(Clone)]
+pub struct ErrorSpec {
+    pub message: String,
+    pub payload: Cell,
+    pub escape: Option<usize>,
+    pub location: Span,
+    pub backtrace: Vec<FrameSpec>,
+}
+
 #[derive(Clone)]
 pub struct LambdaSpec {
+    pub name: Option<Rc<String>>,
     pub arguments: Vec<Cell>,
+    pub argument_types: Vec<Type>,
     pub body: Vec<Cell>,
     pub environment: Environment,
+    pub code: Option<Rc<Chunk>>,
+}
+
+pub enum FileHandle {
+    Reader(BufReader<fs::File>),
+    Writer(fs::File),
+    Closed,
+}
+
+pub struct FileSpec {
+    pub path: String,
+    pub handle: FileHandle,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Bool(bool),
+    Integer(i64),
+    Char(char),
+    Str(String),
+    Symbol(String),
+}
+
+impl Key {
+    pub fn from_cell(c: &Cell) -> Option<Key> {
+        match c {
+            &Bool(b)       => Some(Key::Bool(b)),
+            &Integer(i)    => Some(Key::Integer(i)),
+            &Char(c)       => Some(Key::Char(c)),
+            &Str(ref s)    => Some(Key::Str(s.clone())),
+            &Symbol(ref s) => Some(Key::Symbol(s.clone())),
+            _              => None,
+        }
+    }
+
+    pub fn to_cell(&self) -> Cell {
+        match self {
+            &Key::Bool(b)       => Bool(b),
+            &Key::Integer(i)    => Integer(i),
+            &Key::Char(c)       => Char(c),
+            &Key::Str(ref s)    => Str(s.clone()),
+            &Key::Symbol(ref s) => Symbol(s.clone()),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -40,42 +153,118 @@ pub enum Cell {
     Char(char),
     Bool(bool),
     Str(String),
-    Sexpr(Vec<Cell>),
+    Sexpr(Vec<Cell>, Span),
     Qexpr(Vec<Cell>),
-    Error(String),
+    Error(Box<ErrorSpec>),
     Builtin(&'static BuiltinFunctionSpec),
     CurriedBuiltin(Box<CurriedBuiltinSpec>),
+    Native(Box<NativeSpec>),
     Lambda(Box<LambdaSpec>),
+    Macro(Box<LambdaSpec>),
+    Type(Box<Type>),
+    Map(Box<BTreeMap<Key, Cell>>),
+    File(Rc<RefCell<FileSpec>>),
+    Continuation(usize),
 }
 
 #[test]
 fn test_cell_size() {
-    assert_eq!(mem::size_of::<Cell>(), 32);
+    assert_eq!(mem::size_of::<Cell>(), 40);
 }
 
 impl PartialEq for Cell {
     fn eq(&self, other: &Cell) -> bool {
         match (self, other) {
-            (&Nil, &Nil)                       => true,
-            (&Symbol(ref a), &Symbol(ref b))   => *a == *b,
-            (&Integer(ref a), &Integer(ref b)) => *a == *b,
-            (&Float(ref a), &Float(ref b))     => *a == *b,
-            (&Char(ref a), &Char(ref b))       => *a == *b,
-            (&Bool(ref a), &Bool(ref b))       => *a == *b,
-            (&Str(ref a), &Str(ref b))         => *a == *b,
-            (&Sexpr(ref a), &Sexpr(ref b))     => *a == *b,
-            (&Qexpr(ref a), &Qexpr(ref b))     => *a == *b,
-            (&Error(ref a), &Error(ref b))     => *a == *b,
-            (&Builtin(ref a), &Builtin(ref b)) => a.func as *const u8 == b.func as *const u8,
+            (&Nil, &Nil)                         => true,
+            (&Symbol(ref a), &Symbol(ref b))     => *a == *b,
+            (&Integer(ref a), &Integer(ref b))   => *a == *b,
+            (&Float(ref a), &Float(ref b))       => *a == *b,
+            (&Char(ref a), &Char(ref b))         => *a == *b,
+            (&Bool(ref a), &Bool(ref b))         => *a == *b,
+            (&Str(ref a), &Str(ref b))           => *a == *b,
+            (&Sexpr(ref a, _), &Sexpr(ref b, _)) => *a == *b,
+            (&Qexpr(ref a), &Qexpr(ref b))       => *a == *b,
+            (&Error(ref a), &Error(ref b))       => a.message == b.message,
+            (&Builtin(a), &Builtin(b))           => a as *const BuiltinFunctionSpec == b as *const BuiltinFunctionSpec,
             (&CurriedBuiltin(ref a),
-             &CurriedBuiltin(ref b))           => a.builtin.func as *const u8 == b.builtin.func as *const u8,
-            (&Lambda(ref a), &Lambda(ref b))   => a.arguments == b.arguments && a.body == b.body,
+             &CurriedBuiltin(ref b))             => a.builtin as *const BuiltinFunctionSpec == b.builtin as *const BuiltinFunctionSpec,
+            (&Native(ref a), &Native(ref b))     => &*a.function as *const NativeFunctionSpec == &*b.function as *const NativeFunctionSpec,
+            (&Lambda(ref a), &Lambda(ref b))     |
+            (&Macro(ref a), &Macro(ref b))       => a.arguments == b.arguments &&
+                                                    a.argument_types == b.argument_types &&
+                                                    a.body == b.body,
+            (&Cell::Type(ref a),
+             &Cell::Type(ref b))                 => a == b,
+            (&Map(ref a), &Map(ref b))           => a == b,
+            (&File(ref a), &File(ref b))         => &**a as *const RefCell<FileSpec> == &**b as *const RefCell<FileSpec>,
+            (&Continuation(a), &Continuation(b)) => a == b,
             _ => false,
         }
     }
 }
 
 impl Cell {
+    pub fn error(message: String) -> Cell {
+        Cell::error_with_payload(message, Nil)
+    }
+
+    pub fn error_with_payload(message: String, payload: Cell) -> Cell {
+        Error(Box::new(ErrorSpec {
+            message: message,
+            payload: payload,
+            escape: None,
+            location: None,
+            backtrace: Vec::new(),
+        }))
+    }
+
+    // An error from outside the evaluator, like a parse error in a loaded file
+    pub fn from_error(e: RlispError) -> Cell {
+        Error(Box::new(ErrorSpec {
+            message: e.message,
+            payload: Nil,
+            escape: None,
+            location: e.location.map(Rc::new),
+            backtrace: Vec::new(),
+        }))
+    }
+
+    // Unwinds like an error until it reaches the call/cc that created the continuation
+    pub fn escape(continuation: usize, value: Cell) -> Cell {
+        Error(Box::new(ErrorSpec {
+            message: "continuation called outside of its call/cc".to_string(),
+            payload: value,
+            escape: Some(continuation),
+            location: None,
+            backtrace: Vec::new(),
+        }))
+    }
+
+    // Unwinds all the way to the host, try can't catch it
+    pub fn limit(message: &str) -> Cell {
+        Error(Box::new(ErrorSpec {
+            message: message.to_string(),
+            payload: Nil,
+            escape: Some(LIMIT_ESCAPE),
+            location: None,
+            backtrace: Vec::new(),
+        }))
+    }
+
+    pub fn is_limit(&self) -> bool {
+        match *self {
+            Error(ref e) => e.escape == Some(LIMIT_ESCAPE),
+            _            => false,
+        }
+    }
+
+    pub fn is_escape(&self) -> bool {
+        match *self {
+            Error(ref e) => e.escape.is_some(),
+            _            => false,
+        }
+    }
+
     pub fn get_type(&self) -> Type {
         match *self {
             Nil               => NilT,
@@ -85,37 +274,52 @@ impl Cell {
             Char(_)           => CharT,
             Bool(_)           => BoolT,
             Str(_)            => StringT,
-            Sexpr(ref v)      => RSexprT(v.iter().map(|e| e.get_type()).collect()),
+            Sexpr(ref v, _)   => RSexprT(v.iter().map(|e| e.get_type()).collect()),
             Qexpr(ref v)      => RQexprT(v.iter().map(|e| e.get_type()).collect()),
             Error(_)          => ErrorT,
             Builtin(_)        => BuiltinT,
             CurriedBuiltin(_) => BuiltinT,
+            Native(_)         => BuiltinT,
             Lambda(_)         => LambdaT,
+            Macro(_)          => MacroT,
+            Cell::Type(_)     => TypeT,
+            Map(_)            => MapT,
+            File(_)           => FileT,
+            Continuation(_)   => ContinuationT,
         }
     }
 
     pub fn is_type(&self, t: &Type) -> bool {
         match (self, t) {
-            (&Nil, &NilT)                     => true,
-            (&Symbol(_), &SymbolT)            => true,
-            (&Integer(_), &IntegerT)          => true,
-            (&Float(_), &FloatT)              => true,
-            (&Char(_), &CharT)                => true,
-            (&Bool(_), &BoolT)                => true,
-            (&Str(_), &StringT)               => true,
-            (&Sexpr(ref v), &SexprT(ref vt))  => v.iter().zip(vt.iter()).all(|(e, t)| e.is_type(t)),
-            (&Qexpr(ref v), &QexprT(ref vt))  => v.iter().zip(vt.iter()).all(|(e, t)| e.is_type(t)),
-            (&Sexpr(ref v), &RSexprT(ref vt)) => v.iter().zip(vt.iter()).all(|(e, t)| e.is_type(t)),
-            (&Qexpr(ref v), &RQexprT(ref vt)) => v.iter().zip(vt.iter()).all(|(e, t)| e.is_type(t)),
-            (&Error(_), &ErrorT)              => true,
-            (&Builtin(_), &BuiltinT)          => true,
-            (&CurriedBuiltin(_), &BuiltinT)   => true,
-            (&Lambda(_), &LambdaT)            => true,
-            (_, &AnyT)                        => true,
-            (_, &ElipsisT(inner))             => self.is_type(inner),
-            (_, &OptionalT(inner))            => self.is_type(inner),
-            (_, &OrT(i1, i2))                 => self.is_type(i1) || self.is_type(i2),
-            _                                 => false,
+            (&Nil, &NilT)                        => true,
+            (&Symbol(_), &SymbolT)               => true,
+            (&Integer(_), &IntegerT)             => true,
+            (&Float(_), &FloatT)                 => true,
+            (&Char(_), &CharT)                   => true,
+            (&Bool(_), &BoolT)                   => true,
+            (&Str(_), &StringT)                  => true,
+            (&Sexpr(ref v, _), &SexprT(vt))      => types::matches(vt, v),
+            (&Qexpr(ref v), &QexprT(vt))         => types::matches(vt, v),
+            (&Sexpr(ref v, _), &RSexprT(ref vt)) => types::matches(vt, v),
+            (&Qexpr(ref v), &RQexprT(ref vt))    => types::matches(vt, v),
+            (&Error(_), &ErrorT)                 => true,
+            (&Builtin(_), &BuiltinT)             => true,
+            (&CurriedBuiltin(_), &BuiltinT)      => true,
+            (&Native(_), &BuiltinT)              => true,
+            (&Lambda(_), &LambdaT)               => true,
+            (&Macro(_), &MacroT)                 => true,
+            (&Cell::Type(_), &TypeT)             => true,
+            (&Map(_), &MapT)                     => true,
+            (&File(_), &FileT)                   => true,
+            (&Continuation(_), &ContinuationT)   => true,
+            (_, &AnyT)                           => true,
+            (_, &ElipsisT(inner))                => self.is_type(inner),
+            (_, &OptionalT(inner))               => self.is_type(inner),
+            (_, &OrT(i1, i2))                    => self.is_type(i1) || self.is_type(i2),
+            (_, &RElipsisT(ref inner))           => self.is_type(inner),
+            (_, &ROptionalT(ref inner))          => self.is_type(inner),
+            (_, &ROrT(ref i1, ref i2))           => self.is_type(i1) || self.is_type(i2),
+            _                                    => false,
         }
     }
 
@@ -127,10 +331,10 @@ impl Cell {
             &Symbol(ref sym) => Owned(format!("{}", sym)),
             &Integer(ref i)  => Owned(format!("{}", i)),
             &Float(ref fl)   => Owned(format!("{:.2}", fl)),
-            &Char(ref c)     => Owned(format!("'{}'", c)),
+            &Char(ref c)     => Owned(format!("'{}'", parser::escape(&c.to_string(), '\''))),
             &Bool(ref b)     => if *b { Borrowed("#t") } else { Borrowed("#f") },
-            &Str(ref s)      => Owned(format!("\"{}\"", s)),
-            &Sexpr(ref v)    => {
+            &Str(ref s)      => Owned(format!("\"{}\"", parser::escape(s, '"'))),
+            &Sexpr(ref v, _) => {
                 let mut temp: String = "(".to_string();
                 for i in 0..v.len() {
                     if i == v.len() - 1 {
@@ -154,7 +358,7 @@ impl Cell {
                 temp.push_str("}");
                 Owned(temp)
             },
-            &Error(ref e)    => Owned(format!("Error: {}", e)),
+            &Error(ref e)    => Owned(format!("Error: {}", e.message)),
             &Builtin(ref f)  => {
                 let mut temp: String = String::new();
                 for (i, t) in f.argument_types.iter().enumerate() {
@@ -179,8 +383,57 @@ impl Cell {
                 }
                 Owned(format!("func: ({} {})", cb.builtin.name, temp))
             },
-            &Lambda(ref l) => Owned(format!("(lambda {} {})",
-                                    Qexpr(l.arguments.clone()), Qexpr(l.body.clone()))),
+            &Native(box ref native) => {
+                let argument_types = &native.function.argument_types;
+                let mut temp: String = String::new();
+                for (i, t) in argument_types.iter().enumerate() {
+                    if i >= native.bound_args.len() {
+                        if i == argument_types.len() - 1 {
+                            temp.push_str(&format!("{}", t)[..]);
+                        } else {
+                            temp.push_str(&format!("{} ", t)[..]);
+                        }
+                    }
+                }
+                Owned(format!("func: ({} {})", native.function.name, temp))
+            },
+            &Lambda(ref l) | &Macro(ref l) => {
+                let mut arguments = Vec::new();
+                for (a, t) in l.arguments.iter().zip(l.argument_types.iter()) {
+                    arguments.push(a.clone());
+                    if let &Symbol(ref s) = a {
+                        if *t != types::default_argument_type(s) {
+                            arguments.push(Cell::Type(Box::new(t.clone())));
+                        }
+                    }
+                }
+                let kind = match self {
+                    &Macro(_) => "macro",
+                    _         => "lambda",
+                };
+                Owned(format!("({} {} {})", kind, Qexpr(arguments), Qexpr(l.body.clone())))
+            },
+            &Cell::Type(ref t) => Owned(format!(":{}", t)),
+            &Map(ref m) => {
+                let mut temp: String = "#{".to_string();
+                for (i, (k, v)) in m.iter().enumerate() {
+                    if i == m.len() - 1 {
+                        temp.push_str(&format!("{} {}", k.to_cell(), v)[..]);
+                    } else {
+                        temp.push_str(&format!("{} {} ", k.to_cell(), v)[..]);
+                    }
+                }
+                temp.push_str("}");
+                Owned(temp)
+            },
+            &File(ref f) => {
+                let f = f.borrow();
+                match f.handle {
+                    FileHandle::Closed => Owned(format!("file: {} (closed)", f.path)),
+                    _                  => Owned(format!("file: {}", f.path)),
+                }
+            },
+            &Continuation(id) => Owned(format!("continuation: {}", id)),
         }
     }
 }
@@ -207,10 +460,20 @@ fn test_fmt() {
     assert_eq!(format!("{}", &Bool(true)), "#t");
     assert_eq!(format!("{}", &Bool(false)), "#f");
     assert_eq!(format!("{}", &Str("Hej".to_string())), "\"Hej\"");
-    assert_eq!(format!("{}", &Sexpr(vec![Nil, Integer(1)])), "(() 1)");
+    assert_eq!(format!("{}", &Str("a \"b\"\n\tc\\\r".to_string())), "\"a \\\"b\\\"\\n\\tc\\\\\\u{d}\"");
+    assert_eq!(format!("{}", &Char('\'')), "'\\''");
+    assert_eq!(format!("{}", &Char('"')), "'\"'");
+    assert_eq!(format!("{}", &Cell::Type(Box::new(RQexprT(vec![RElipsisT(Box::new(IntegerT))])))), ":{Integer...}");
+    assert_eq!(format!("{}", &Sexpr(vec![Nil, Integer(1)], None)), "(() 1)");
     assert_eq!(format!("{}", &Qexpr(vec![Nil, Integer(1)])), "{() 1}");
-    assert_eq!(format!("{}", &Sexpr(Vec::new())), "()");
+    assert_eq!(format!("{}", &Sexpr(Vec::new(), None)), "()");
     assert_eq!(format!("{}", &Qexpr(Vec::new())), "{}");
-    assert_eq!(format!("{}", &Error("Error".to_string())), "Error: Error");
+    assert_eq!(format!("{}", &Cell::error("Error".to_string())), "Error: Error");
     assert_eq!(format!("{}", &Float(5.0)), "5.00");
+
+    let mut m = BTreeMap::new();
+    m.insert(Key::Str("b".to_string()), Qexpr(vec![Integer(1)]));
+    m.insert(Key::Integer(2), Char('a'));
+    assert_eq!(format!("{}", &Map(Box::new(m))), "#{2 'a' \"b\" {1}}");
+    assert_eq!(format!("{}", &Map(Box::new(BTreeMap::new()))), "#{}");
 }
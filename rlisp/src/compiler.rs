@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cell::Cell;
+use environment::Environment;
+use gc;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Op {
+    // Push constants[i]
+    Const(usize),
+    // Push the argument in slot i
+    Local(usize),
+    // Push the value of the symbol in constants[i]
+    Lookup(usize),
+    // The procedure is on the stack, when it is a special form or a macro
+    // the form in constants[i] is handed to the evaluator and we jump to end
+    Apply { form: usize, end: usize, tail: bool },
+    // Only in tail position, falls through when the procedure is the if
+    // builtin and otherwise hands the form to the evaluator
+    If(usize),
+    // Pop the condition and jump when it is false
+    Branch(usize),
+    Jump(usize),
+    // Call the procedure below the n arguments on the stack, the form in
+    // constants[i] provides the location
+    Call { argc: usize, form: usize, tail: bool },
+    Return,
+}
+
+pub struct Chunk {
+    pub ops: Vec<Op>,
+    pub constants: Vec<Cell>,
+    // The names of the arguments, shared with every environment the chunk runs in
+    pub arguments: Rc<Vec<String>>,
+    // The code for partial applications, by the number of arguments bound
+    partials: RefCell<HashMap<usize, Rc<Chunk>>>,
+}
+
+struct Compiler<'a> {
+    arguments: &'a [Cell],
+    ops: Vec<Op>,
+    constants: Vec<Cell>,
+}
+
+// Bodies holding values that keep environments alive are left to the evaluator
+pub fn compile(env: &Environment, arguments: &[Cell], body: &[Cell]) -> Option<Rc<Chunk>> {
+    if !env.context().compile_enabled() || !body.iter().all(compilable) {
+        return None;
+    }
+
+    let mut compiler = Compiler {
+        arguments: arguments,
+        ops: Vec::new(),
+        constants: Vec::new(),
+    };
+
+    compiler.form(&Cell::Sexpr(body.to_vec(), None), true);
+    compiler.ops.push(Op::Return);
+
+    Some(Rc::new(Chunk {
+        ops: compiler.ops,
+        constants: compiler.constants,
+        arguments: Rc::new(arguments.iter().map(|a| match a {
+            &Cell::Symbol(ref s) => s.clone(),
+            _                    => String::new(),
+        }).collect()),
+        partials: RefCell::new(HashMap::new()),
+    }))
+}
+
+// Every partial application of a lambda binding the same number of arguments shares its code
+pub fn partial(chunk: &Chunk, env: &Environment, arguments: &[Cell], body: &[Cell], bound: usize) -> Option<Rc<Chunk>> {
+    if !env.context().compile_enabled() {
+        return None;
+    }
+
+    if let Some(code) = chunk.partials.borrow().get(&bound) {
+        return Some(code.clone());
+    }
+
+    let code = compile(env, &arguments[bound..], body);
+
+    if let Some(ref code) = code {
+        chunk.partials.borrow_mut().insert(bound, code.clone());
+    }
+
+    code
+}
+
+fn compilable(c: &Cell) -> bool {
+    match c {
+        &Cell::Sexpr(ref v, _) |
+        &Cell::Qexpr(ref v)    => v.iter().all(compilable),
+        &Cell::Error(_)        => false,
+        c                      => {
+            let mut refs = Vec::new();
+            gc::trace(c, &mut refs);
+            refs.is_empty()
+        },
+    }
+}
+
+impl<'a> Compiler<'a> {
+    fn form(&mut self, c: &Cell, tail: bool) {
+        match c {
+            &Cell::Symbol(ref s) => {
+                let slot = self.arguments.iter().position(|a| match a {
+                    &Cell::Symbol(ref a) => a == s,
+                    _                    => false,
+                });
+
+                let op = match slot {
+                    Some(slot) => Op::Local(slot),
+                    None       => Op::Lookup(self.constant(c)),
+                };
+                self.ops.push(op);
+            },
+            &Cell::Sexpr(ref v, _) => match &v[..] {
+                [Cell::Symbol(ref s), ref test, ref then, ref otherwise] if tail && s == "if" => {
+                    self.if_form(c, test, then, otherwise);
+                },
+                [ref procedure, args..] => self.call(c, procedure, args, tail),
+                []                      => self.literal(c),
+            },
+            c => self.literal(c),
+        }
+    }
+
+    fn literal(&mut self, c: &Cell) {
+        let i = self.constant(c);
+        self.ops.push(Op::Const(i));
+    }
+
+    fn call(&mut self, c: &Cell, procedure: &Cell, args: &[Cell], tail: bool) {
+        let form = self.constant(c);
+
+        self.form(procedure, false);
+        let apply = self.ops.len();
+        self.ops.push(Op::Apply { form: form, end: 0, tail: tail });
+
+        for arg in args.iter() {
+            self.form(arg, false);
+        }
+
+        self.ops.push(Op::Call { argc: args.len(), form: form, tail: tail });
+
+        let end = self.ops.len();
+        self.ops[apply] = Op::Apply { form: form, end: end, tail: tail };
+    }
+
+    fn if_form(&mut self, c: &Cell, test: &Cell, then: &Cell, otherwise: &Cell) {
+        let form = self.constant(c);
+
+        self.form(&Cell::Symbol("if".to_string()), false);
+        self.ops.push(Op::If(form));
+
+        self.form(test, false);
+        let branch = self.ops.len();
+        self.ops.push(Op::Branch(0));
+
+        self.branch(then);
+        let jump = self.ops.len();
+        self.ops.push(Op::Jump(0));
+
+        let otherwise_start = self.ops.len();
+        self.branch(otherwise);
+
+        let end = self.ops.len();
+        self.ops[branch] = Op::Branch(otherwise_start);
+        self.ops[jump] = Op::Jump(end);
+    }
+
+    // A literal list as a branch is evaluated as code, like the if builtin does
+    fn branch(&mut self, c: &Cell) {
+        match c {
+            &Cell::Qexpr(ref v) => self.form(&Cell::Sexpr(v.clone(), None), true),
+            c                   => self.form(c, true),
+        }
+    }
+
+    fn constant(&mut self, c: &Cell) -> usize {
+        self.constants.push(c.clone());
+        self.constants.len() - 1
+    }
+}
+
+#[test]
+fn test_compile() {
+    let env = Environment::new();
+    let x = Cell::Symbol("x".to_string());
+    let plus = Cell::Symbol("+".to_string());
+
+    let chunk = compile(&env, &[x.clone()], &[plus.clone(), x.clone(), Cell::Integer(1)]).unwrap();
+
+    assert_eq!(chunk.ops, vec![Op::Lookup(1),
+                               Op::Apply { form: 0, end: 5, tail: true },
+                               Op::Local(0),
+                               Op::Const(2),
+                               Op::Call { argc: 2, form: 0, tail: true },
+                               Op::Return]);
+    assert_eq!(chunk.constants[1], plus);
+    assert_eq!(chunk.constants[2], Cell::Integer(1));
+}
+
+#[test]
+fn test_compile_if() {
+    let env = Environment::new();
+    let x = Cell::Symbol("x".to_string());
+    let body = [Cell::Symbol("if".to_string()), x.clone(), Cell::Integer(1), Cell::Integer(2)];
+
+    let chunk = compile(&env, &[x.clone()], &body).unwrap();
+
+    assert_eq!(chunk.ops, vec![Op::Lookup(1),
+                               Op::If(0),
+                               Op::Local(0),
+                               Op::Branch(6),
+                               Op::Const(2),
+                               Op::Jump(7),
+                               Op::Const(3),
+                               Op::Return]);
+}
+
+#[test]
+fn test_not_compilable() {
+    let env = Environment::new();
+    let x = Cell::Symbol("x".to_string());
+
+    assert!(compile(&env, &[x.clone()], &[Cell::error("e".to_string())]).is_none());
+
+    env.context().set_compile(false);
+    assert!(compile(&env, &[x.clone()], &[x.clone()]).is_none());
+}
+
+#[test]
+fn test_partial() {
+    let env = Environment::new();
+    let x = Cell::Symbol("x".to_string());
+    let y = Cell::Symbol("y".to_string());
+    let arguments = [x.clone(), y.clone()];
+    let body = [Cell::Symbol("+".to_string()), x.clone(), y.clone()];
+
+    let chunk = compile(&env, &arguments, &body).unwrap();
+    let first = partial(&chunk, &env, &arguments, &body, 1).unwrap();
+    let second = partial(&chunk, &env, &arguments, &body, 1).unwrap();
+
+    assert_eq!(&*first as *const Chunk, &*second as *const Chunk);
+    assert_eq!(*first.arguments, vec!["y".to_string()]);
+}
@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use cell::Cell;
+
+// Checking the clock on every step would slow down evaluation
+const DEADLINE_INTERVAL: usize = 64;
+
+// Each nested call takes a few kilobytes of native stack, a depth limit of
+// this keeps deep recursion an error instead of an overflow even on the 2MB
+// stack of a thread. It's not the default, opt in with set_limits.
+pub const THREAD_DEPTH: usize = 256;
+
+#[derive(Clone, Default)]
+pub struct Limits {
+    pub steps: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub depth: Option<usize>,
+}
+
+struct ContextImpl {
+    loading: Vec<PathBuf>,
+    modules: HashMap<PathBuf, (String, Cell)>,
+    symbols: usize,
+    continuations: usize,
+    active: Vec<usize>,
+    compile: bool,
+    limits: Limits,
+    steps: usize,
+    depth: usize,
+    deadline: Option<Instant>,
+}
+
+// State shared by every environment of one interpreter
+#[derive(Clone)]
+pub struct Context(Rc<RefCell<ContextImpl>>);
+
+impl Context {
+    pub fn new() -> Context {
+        Context(Rc::new(RefCell::new(ContextImpl {
+            loading: Vec::new(),
+            modules: HashMap::new(),
+            symbols: 0,
+            continuations: 0,
+            active: Vec::new(),
+            compile: true,
+            limits: Limits::default(),
+            steps: 0,
+            depth: 0,
+            deadline: None,
+        })))
+    }
+
+    // Relative paths are resolved against the directory of the file being loaded
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        let &Context(ref context) = self;
+
+        if path.is_relative() {
+            if let Some(dir) = context.borrow().loading.last().and_then(|p| p.parent()) {
+                return dir.join(path);
+            }
+        }
+
+        path.to_path_buf()
+    }
+
+    pub fn enter(&self, path: &Path) -> Result<PathBuf, String> {
+        let &Context(ref context) = self;
+
+        let path = match fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(e)   => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let mut context = context.borrow_mut();
+
+        if let Some(i) = context.loading.iter().position(|p| *p == path) {
+            let mut cycle = context.loading[i..].iter()
+                                                .map(|p| format!("{}", p.display()))
+                                                .collect::<Vec<String>>();
+            cycle.push(format!("{}", path.display()));
+            return Err(format!("cycle detected: {}", cycle.join(" -> ")));
+        }
+
+        context.loading.push(path.clone());
+
+        Ok(path)
+    }
+
+    pub fn leave(&self) {
+        let &Context(ref context) = self;
+        context.borrow_mut().loading.pop();
+    }
+
+    pub fn module(&self, path: &Path) -> Option<(String, Cell)> {
+        let &Context(ref context) = self;
+        let module = context.borrow().modules.get(path).cloned();
+        module
+    }
+
+    pub fn insert_module(&self, path: PathBuf, name: String, module: Cell) {
+        let &Context(ref context) = self;
+        context.borrow_mut().modules.insert(path, (name, module));
+    }
+
+    pub fn next_symbol(&self) -> usize {
+        let &Context(ref context) = self;
+        let mut context = context.borrow_mut();
+        context.symbols += 1;
+        context.symbols
+    }
+
+    pub fn enter_continuation(&self) -> usize {
+        let &Context(ref context) = self;
+        let mut context = context.borrow_mut();
+        context.continuations += 1;
+        let id = context.continuations;
+        context.active.push(id);
+        id
+    }
+
+    pub fn leave_continuation(&self) {
+        let &Context(ref context) = self;
+        context.borrow_mut().active.pop();
+    }
+
+    pub fn is_active_continuation(&self, id: usize) -> bool {
+        let &Context(ref context) = self;
+        let active = context.borrow().active.contains(&id);
+        active
+    }
+
+    pub fn compile_enabled(&self) -> bool {
+        let &Context(ref context) = self;
+        let compile = context.borrow().compile;
+        compile
+    }
+
+    pub fn set_compile(&self, compile: bool) {
+        let &Context(ref context) = self;
+        context.borrow_mut().compile = compile;
+    }
+
+    pub fn set_limits(&self, limits: Limits) {
+        let &Context(ref context) = self;
+        context.borrow_mut().limits = limits;
+    }
+
+    // Called when the host starts an evaluation, the limits apply to each one separately
+    pub fn start(&self) {
+        let &Context(ref context) = self;
+        let mut context = context.borrow_mut();
+        context.steps = 0;
+        context.depth = 0;
+        context.deadline = context.limits.timeout.map(|t| Instant::now() + t);
+    }
+
+    pub fn step(&self) -> Option<Cell> {
+        let &Context(ref context) = self;
+        let mut context = context.borrow_mut();
+        context.steps += 1;
+
+        if let Some(steps) = context.limits.steps {
+            if context.steps > steps {
+                return Some(Cell::limit("step limit exceeded"));
+            }
+        }
+
+        if let Some(deadline) = context.deadline {
+            if context.steps % DEADLINE_INTERVAL == 0 && Instant::now() >= deadline {
+                return Some(Cell::limit("time limit exceeded"));
+            }
+        }
+
+        None
+    }
+
+    pub fn enter_call(&self) -> Option<Cell> {
+        let &Context(ref context) = self;
+        let mut context = context.borrow_mut();
+
+        if let Some(depth) = context.limits.depth {
+            if context.depth >= depth {
+                return Some(Cell::limit("call depth limit exceeded"));
+            }
+        }
+
+        context.depth += 1;
+        None
+    }
+
+    pub fn leave_call(&self) {
+        let &Context(ref context) = self;
+        context.borrow_mut().depth -= 1;
+    }
+
+    // The cached modules hold on to their environments
+    pub fn clear(&self) {
+        let &Context(ref context) = self;
+        let _modules = {
+            let mut context = context.borrow_mut();
+            mem::replace(&mut context.modules, HashMap::new())
+        };
+    }
+}
+
+#[test]
+fn test_limits() {
+    let context = Context::new();
+    context.set_limits(Limits { steps: Some(2), timeout: None, depth: Some(1) });
+    context.start();
+
+    assert!(context.step().is_none());
+    assert!(context.step().is_none());
+    assert_eq!(context.step(), Some(Cell::limit("step limit exceeded")));
+
+    assert!(context.enter_call().is_none());
+    assert!(context.enter_call().unwrap().is_limit());
+    context.leave_call();
+
+    context.start();
+    assert!(context.step().is_none());
+    assert!(context.enter_call().is_none());
+
+    context.set_limits(Limits { steps: None, timeout: Some(Duration::from_millis(0)), depth: None });
+    context.start();
+    let exceeded = (0..DEADLINE_INTERVAL).filter_map(|_| context.step()).next();
+    assert_eq!(exceeded, Some(Cell::limit("time limit exceeded")));
+}
+
+#[test]
+fn test_resolve() {
+    let context = Context::new();
+    assert_eq!(context.resolve(Path::new("a.rl")), PathBuf::from("a.rl"));
+
+    let dir = ::std::env::temp_dir();
+    let path = context.enter(&dir).unwrap();
+    assert_eq!(context.resolve(Path::new("a.rl")), path.parent().unwrap().join("a.rl"));
+    assert_eq!(context.resolve(&dir), dir);
+
+    assert!(context.enter(&dir).unwrap_err().starts_with("cycle detected: "));
+
+    context.leave();
+    assert_eq!(context.resolve(Path::new("a.rl")), PathBuf::from("a.rl"));
+}
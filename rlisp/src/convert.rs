@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use cell::Cell;
+use error::{ErrorKind, RlispError};
+use value::Value;
+
+#[cfg(test)]
+use cell::Key;
+
+pub trait IntoCell {
+    fn into_cell(self) -> Cell;
+}
+
+pub trait FromCell: Sized {
+    fn from_cell(c: &Cell) -> Result<Self, RlispError>;
+}
+
+// The arguments of Rlisp::call, a tuple gives one argument per element
+pub trait IntoArgs {
+    fn into_args(self) -> Vec<Cell>;
+}
+
+// Types that can be used as the keys of a map, sealed since only the types
+// with a Key variant can be implemented
+pub trait MapKey: private::Sealed + IntoCell + FromCell + Eq + Hash {}
+
+impl MapKey for i64 {}
+impl MapKey for bool {}
+impl MapKey for char {}
+impl MapKey for String {}
+
+mod private {
+    use cell::Key;
+
+    pub trait Sealed {
+        fn into_key(self) -> Key;
+    }
+
+    impl Sealed for i64 {
+        fn into_key(self) -> Key {
+            Key::Integer(self)
+        }
+    }
+
+    impl Sealed for bool {
+        fn into_key(self) -> Key {
+            Key::Bool(self)
+        }
+    }
+
+    impl Sealed for char {
+        fn into_key(self) -> Key {
+            Key::Char(self)
+        }
+    }
+
+    impl Sealed for String {
+        fn into_key(self) -> Key {
+            Key::Str(self)
+        }
+    }
+}
+
+fn mismatch(expected: &str, c: &Cell) -> RlispError {
+    RlispError::new(ErrorKind::Conversion, format!("expected {}, got {}", expected, c.get_type()), None)
+}
+
+fn within<T: FromCell>(context: String, c: &Cell) -> Result<T, RlispError> {
+    T::from_cell(c).map_err(|e| RlispError::new(ErrorKind::Conversion, format!("{}: {}", context, e.message), None))
+}
+
+impl IntoCell for Cell {
+    fn into_cell(self) -> Cell {
+        self
+    }
+}
+
+impl IntoCell for i64 {
+    fn into_cell(self) -> Cell {
+        Cell::Integer(self)
+    }
+}
+
+// Plain integer literals default to i32
+impl IntoCell for i32 {
+    fn into_cell(self) -> Cell {
+        Cell::Integer(self as i64)
+    }
+}
+
+impl IntoCell for f64 {
+    fn into_cell(self) -> Cell {
+        Cell::Float(self)
+    }
+}
+
+impl IntoCell for bool {
+    fn into_cell(self) -> Cell {
+        Cell::Bool(self)
+    }
+}
+
+impl IntoCell for char {
+    fn into_cell(self) -> Cell {
+        Cell::Char(self)
+    }
+}
+
+impl IntoCell for String {
+    fn into_cell(self) -> Cell {
+        Cell::Str(self)
+    }
+}
+
+impl<'a> IntoCell for &'a str {
+    fn into_cell(self) -> Cell {
+        Cell::Str(self.to_string())
+    }
+}
+
+impl<T: IntoCell> IntoCell for Vec<T> {
+    fn into_cell(self) -> Cell {
+        Cell::Qexpr(self.into_iter().map(IntoCell::into_cell).collect())
+    }
+}
+
+impl<T: IntoCell> IntoCell for Option<T> {
+    fn into_cell(self) -> Cell {
+        match self {
+            Some(v) => v.into_cell(),
+            None    => Cell::Nil,
+        }
+    }
+}
+
+// An Err becomes an error value, raised like the errors of the builtins
+impl<T: IntoCell> IntoCell for Result<T, RlispError> {
+    fn into_cell(self) -> Cell {
+        match self {
+            Ok(v)  => v.into_cell(),
+            Err(e) => Cell::from_error(e),
+        }
+    }
+}
+
+impl<K: MapKey, V: IntoCell> IntoCell for HashMap<K, V> {
+    fn into_cell(self) -> Cell {
+        let mut map = BTreeMap::new();
+
+        for (k, v) in self.into_iter() {
+            map.insert(k.into_key(), v.into_cell());
+        }
+
+        Cell::Map(Box::new(map))
+    }
+}
+
+impl FromCell for Cell {
+    fn from_cell(c: &Cell) -> Result<Cell, RlispError> {
+        Ok(c.clone())
+    }
+}
+
+impl FromCell for Value {
+    fn from_cell(c: &Cell) -> Result<Value, RlispError> {
+        Ok(Value::from(c))
+    }
+}
+
+impl FromCell for i64 {
+    fn from_cell(c: &Cell) -> Result<i64, RlispError> {
+        match c {
+            &Cell::Integer(i) => Ok(i),
+            _                 => Err(mismatch("Integer", c)),
+        }
+    }
+}
+
+impl FromCell for i32 {
+    fn from_cell(c: &Cell) -> Result<i32, RlispError> {
+        match c {
+            &Cell::Integer(i) if i as i32 as i64 == i => Ok(i as i32),
+            _                                         => Err(mismatch("Integer in the range of i32", c)),
+        }
+    }
+}
+
+impl FromCell for f64 {
+    fn from_cell(c: &Cell) -> Result<f64, RlispError> {
+        match c {
+            &Cell::Float(f)   => Ok(f),
+            &Cell::Integer(i) => Ok(i as f64),
+            _                 => Err(mismatch("Float", c)),
+        }
+    }
+}
+
+impl FromCell for bool {
+    fn from_cell(c: &Cell) -> Result<bool, RlispError> {
+        match c {
+            &Cell::Bool(b) => Ok(b),
+            _              => Err(mismatch("Bool", c)),
+        }
+    }
+}
+
+impl FromCell for char {
+    fn from_cell(c: &Cell) -> Result<char, RlispError> {
+        match c {
+            &Cell::Char(c) => Ok(c),
+            _              => Err(mismatch("Char", c)),
+        }
+    }
+}
+
+// Symbols convert to their names so maps with symbol keys can be read
+impl FromCell for String {
+    fn from_cell(c: &Cell) -> Result<String, RlispError> {
+        match c {
+            &Cell::Str(ref s)    |
+            &Cell::Symbol(ref s) => Ok(s.clone()),
+            _                    => Err(mismatch("String", c)),
+        }
+    }
+}
+
+impl<T: FromCell> FromCell for Vec<T> {
+    fn from_cell(c: &Cell) -> Result<Vec<T>, RlispError> {
+        match c {
+            &Cell::Qexpr(ref v) => {
+                let mut result = Vec::with_capacity(v.len());
+
+                for (i, e) in v.iter().enumerate() {
+                    result.push(try!(within(format!("element {}", i + 1), e)));
+                }
+
+                Ok(result)
+            },
+            _ => Err(mismatch("List", c)),
+        }
+    }
+}
+
+impl<T: FromCell> FromCell for Option<T> {
+    fn from_cell(c: &Cell) -> Result<Option<T>, RlispError> {
+        match c {
+            &Cell::Nil => Ok(None),
+            c          => T::from_cell(c).map(Some),
+        }
+    }
+}
+
+impl<K: MapKey, V: FromCell> FromCell for HashMap<K, V> {
+    fn from_cell(c: &Cell) -> Result<HashMap<K, V>, RlispError> {
+        match c {
+            &Cell::Map(ref m) => {
+                let mut result = HashMap::new();
+
+                for (k, v) in m.iter() {
+                    let key = k.to_cell();
+                    let value = try!(within(format!("value of {}", key), v));
+                    result.insert(try!(within(format!("key {}", key), &key)), value);
+                }
+
+                Ok(result)
+            },
+            _ => Err(mismatch("Map", c)),
+        }
+    }
+}
+
+impl IntoArgs for () {
+    fn into_args(self) -> Vec<Cell> {
+        Vec::new()
+    }
+}
+
+impl IntoArgs for Vec<Cell> {
+    fn into_args(self) -> Vec<Cell> {
+        self
+    }
+}
+
+macro_rules! tuple_impls {
+    ($($len:expr => ($($n:tt $name:ident)+))+) => {
+        $(
+            impl<$($name: IntoCell),+> IntoArgs for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn into_args(self) -> Vec<Cell> {
+                    let ($($name,)+) = self;
+                    vec![$($name.into_cell()),+]
+                }
+            }
+
+            impl<$($name: IntoCell),+> IntoCell for ($($name,)+) {
+                fn into_cell(self) -> Cell {
+                    Cell::Qexpr(self.into_args())
+                }
+            }
+
+            impl<$($name: FromCell),+> FromCell for ($($name,)+) {
+                fn from_cell(c: &Cell) -> Result<($($name,)+), RlispError> {
+                    match c {
+                        &Cell::Qexpr(ref v) if v.len() == $len => {
+                            Ok(($(try!(within(format!("element {}", $n + 1), &v[$n])),)+))
+                        },
+                        _ => Err(mismatch(&format!("List of {} elements", $len), c)),
+                    }
+                }
+            }
+        )+
+    }
+}
+
+tuple_impls! {
+    1 => (0 A)
+    2 => (0 A 1 B)
+    3 => (0 A 1 B 2 C)
+    4 => (0 A 1 B 2 C 3 D)
+    5 => (0 A 1 B 2 C 3 D 4 E)
+    6 => (0 A 1 B 2 C 3 D 4 E 5 F)
+}
+
+#[test]
+fn test_into_cell() {
+    assert_eq!(1i64.into_cell(), Cell::Integer(1));
+    assert_eq!("a".into_cell(), Cell::Str("a".to_string()));
+    assert_eq!(vec![Some(1i64), None].into_cell(), Cell::Qexpr(vec![Cell::Integer(1), Cell::Nil]));
+    assert_eq!((1i64, 'c', true).into_cell(), Cell::Qexpr(vec![Cell::Integer(1), Cell::Char('c'), Cell::Bool(true)]));
+    assert_eq!(Ok::<i64, RlispError>(1).into_cell(), Cell::Integer(1));
+    assert_eq!(Err::<i64, RlispError>(RlispError::new(ErrorKind::Runtime, "e".to_string(), None)).into_cell(),
+               Cell::error("e".to_string()));
+
+    let mut map = HashMap::new();
+    map.insert("k".to_string(), 2.5);
+
+    let mut expected = BTreeMap::new();
+    expected.insert(Key::Str("k".to_string()), Cell::Float(2.5));
+    assert_eq!(map.into_cell(), Cell::Map(Box::new(expected)));
+}
+
+#[test]
+fn test_from_cell() {
+    let list = Cell::Qexpr(vec![Cell::Integer(1), Cell::Integer(2)]);
+
+    assert_eq!(Vec::<i64>::from_cell(&list), Ok(vec![1, 2]));
+    assert_eq!(<(i64, i64)>::from_cell(&list), Ok((1, 2)));
+    assert_eq!(f64::from_cell(&Cell::Integer(2)), Ok(2.0));
+    assert_eq!(i32::from_cell(&Cell::Integer(1 << 40)).unwrap_err().message, "expected Integer in the range of i32, got Integer");
+    assert_eq!(Option::<char>::from_cell(&Cell::Nil), Ok(None));
+
+    let e = Vec::<bool>::from_cell(&list).unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Conversion);
+    assert_eq!(e.message, "element 1: expected Bool, got Integer");
+
+    let e = <(i64, i64, i64)>::from_cell(&list).unwrap_err();
+    assert_eq!(e.message, "expected List of 3 elements, got {Integer Integer}");
+
+    let mut map = BTreeMap::new();
+    map.insert(Key::Symbol("a".to_string()), Cell::Integer(1));
+    let map = Cell::Map(Box::new(map));
+
+    let m = HashMap::<String, i64>::from_cell(&map).unwrap();
+    assert_eq!(m.get("a"), Some(&1));
+    assert_eq!(HashMap::<String, bool>::from_cell(&map).unwrap_err().message, "value of a: expected Bool, got Integer");
+}
@@ -1,60 +1,302 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::mem;
 use std::rc::Rc;
 use std::rc::Weak;
 
-use cell::Cell;
+use cell::{Cell, Key};
+use context::Context;
+use gc::{self, Heap};
 use globals;
 
 struct EnvironmentImpl {
     table:     HashMap<String, Cell>,
-    enclosing: Option<Weak<RefCell<EnvironmentImpl>>>,
+    // Imported modules live apart from the table, they are only reached as module/name
+    imports:   HashMap<String, Cell>,
+    enclosing: Option<Environment>,
+    heap:      Heap,
+    context:   Context,
+    module:    Option<ModuleSpec>,
+    // The arguments of a compiled lambda call, kept by position instead of in the table
+    arguments: Option<(Rc<Vec<String>>, Vec<Cell>)>,
+}
+
+#[derive(Clone, Default)]
+pub struct ModuleSpec {
+    pub name:    Option<String>,
+    pub exports: Vec<String>,
 }
 
 #[derive(Clone)]
 pub struct Environment(Rc<RefCell<EnvironmentImpl>>);
 
+pub struct WeakEnvironment(Weak<RefCell<EnvironmentImpl>>);
+
 impl Environment {
     pub fn new() -> Environment {
-        Environment(Rc::new(RefCell::new(EnvironmentImpl {
+        let heap = Heap::new();
+        let env = Environment(Rc::new(RefCell::new(EnvironmentImpl {
             table:     HashMap::new(),
+            imports:   HashMap::new(),
             enclosing: None,
-        })))
+            heap:      heap.clone(),
+            context:   Context::new(),
+            module:    None,
+            arguments: None,
+        })));
+        heap.register(&env);
+        env
     }
 
     pub fn make_sub_environment(&self) -> Environment {
+        self.make_environment(None, None)
+    }
+
+    // Definitions inside a module stay in the module instead of going to the top
+    pub fn make_module_environment(&self) -> Environment {
+        self.make_environment(Some(ModuleSpec::default()), None)
+    }
+
+    // The arguments are only moved to the table if one of them is removed
+    pub fn make_argument_environment(&self, names: Rc<Vec<String>>, values: Vec<Cell>) -> Environment {
+        self.make_environment(None, Some((names, values)))
+    }
+
+    fn make_environment(&self, module: Option<ModuleSpec>, arguments: Option<(Rc<Vec<String>>, Vec<Cell>)>) -> Environment {
         let &Environment(ref env) = self;
-        Environment(Rc::new(RefCell::new(EnvironmentImpl {
+        let heap = env.borrow().heap.clone();
+        let context = env.borrow().context.clone();
+
+        if heap.should_collect() {
+            heap.collect();
+        }
+
+        let sub_env = Environment(Rc::new(RefCell::new(EnvironmentImpl {
             table:     HashMap::new(),
-            enclosing: Some(Rc::downgrade(&env)),
-        })))
+            imports:   HashMap::new(),
+            enclosing: Some(self.clone()),
+            heap:      heap.clone(),
+            context:   context,
+            module:    module,
+            arguments: arguments,
+        })));
+        heap.register(&sub_env);
+        sub_env
     }
 
     pub fn lookup(&self, key: &str) -> Cell {
+        if let Some(c) = self.get(key) {
+            return c;
+        }
+
+        if let Some(bfs) = globals::GLOBAL_ENVIROMENT.get(key) {
+            return Cell::Builtin(bfs);
+        }
+
+        match key.find('/') {
+            Some(i) if i > 0 && i < key.len() - 1 => self.lookup_qualified(&key[..i], &key[i+1..]),
+            _                                     => Cell::error(format!("Undefined symbol: {}", key)),
+        }
+    }
+
+    fn lookup_qualified(&self, module: &str, name: &str) -> Cell {
+        match self.imported(module) {
+            Some(Cell::Map(m)) => match m.get(&Key::Symbol(name.to_string())) {
+                Some(c) => c.clone(),
+                None    => Cell::error(format!("{} does not export {}", module, name)),
+            },
+            _ => Cell::error(format!("Undefined symbol: {}/{}", module, name)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Cell> {
+        if let Some(c) = self.get_local(key) {
+            return Some(c);
+        }
+
         let &Environment(ref env) = self;
-        match (env.borrow().table.get(key), &env.borrow().enclosing) {
-            (Some(c), _)         => c.clone(),
-            (None, &Some(ref e)) => Environment(e.upgrade().expect("Internal error")).lookup(key),
-            (None, &None)        => {
-                match globals::GLOBAL_ENVIROMENT.get(key) {
-                    Some(bfs) => Cell::Builtin(bfs),
-                    None      => Cell::Error(format!("Undefined symbol: {}", key))
-                }
-            }
+        match env.borrow().enclosing {
+            Some(ref e) => e.get(key),
+            None        => None,
         }
     }
 
+    // Only this environment, not the ones enclosing it
+    pub fn get_local(&self, key: &str) -> Option<Cell> {
+        let &Environment(ref env) = self;
+        let env = env.borrow();
+
+        if let Some(c) = env.table.get(key) {
+            return Some(c.clone());
+        }
+
+        match env.arguments {
+            Some((ref names, ref values)) => names.iter().position(|n| n == key).map(|i| values[i].clone()),
+            None                          => None,
+        }
+    }
+
+    // The argument in position i, unless the arguments have been moved to the table
+    pub fn argument(&self, i: usize) -> Option<Cell> {
+        let &Environment(ref env) = self;
+        let c = match env.borrow().arguments {
+            Some((_, ref values)) => values.get(i).cloned(),
+            None                  => None,
+        };
+        c
+    }
+
+    fn imported(&self, module: &str) -> Option<Cell> {
+        let &Environment(ref env) = self;
+        match (env.borrow().imports.get(module), &env.borrow().enclosing) {
+            (Some(c), _)         => Some(c.clone()),
+            (None, &Some(ref e)) => e.imported(module),
+            (None, &None)        => None,
+        }
+    }
+
+    pub fn insert_import(&self, module: &str, c: &Cell) {
+        let top = self.top();
+        let &Environment(ref env) = &top;
+        env.borrow_mut().imports.insert(module.to_string(), c.clone());
+    }
+
     pub fn insert(&self, key: &str, c: &Cell) {
         let &Environment(ref env) = self;
-        env.borrow_mut().table.insert(key.to_string(), c.clone());
+        let mut env = env.borrow_mut();
+
+        if let Some((ref names, ref mut values)) = env.arguments {
+            if let Some(i) = names.iter().position(|n| n == key) {
+                values[i] = c.clone();
+                return;
+            }
+        }
+
+        env.table.insert(key.to_string(), c.clone());
+    }
+
+    pub fn remove(&self, key: &str) -> Option<Cell> {
+        let &Environment(ref env) = self;
+        let mut env = env.borrow_mut();
+
+        let arguments = env.arguments.take();
+
+        if let Some((names, values)) = arguments {
+            for (name, value) in names.iter().zip(values.into_iter()) {
+                env.table.insert(name.clone(), value);
+            }
+        }
+
+        env.table.remove(key)
     }
 
     pub fn insert_top(&self, key: &str, c: &Cell) {
+        self.top().insert(key, c);
+    }
+
+    // The outermost environment, or the module environment when inside a module
+    pub fn top(&self) -> Environment {
+        let &Environment(ref env) = self;
+        let env = env.borrow();
+        match env.enclosing {
+            Some(ref e) if env.module.is_none() => e.top(),
+            _                                   => self.clone(),
+        }
+    }
+
+    pub fn root(&self) -> Environment {
+        let &Environment(ref env) = self;
+        match env.borrow().enclosing {
+            Some(ref e) => e.root(),
+            None        => self.clone(),
+        }
+    }
+
+    pub fn declare_module(&self, name: &str, exports: Vec<String>) -> bool {
+        let top = self.top();
+        let &Environment(ref env) = &top;
+        let mut env = env.borrow_mut();
+        match env.module {
+            Some(ref mut module) => {
+                module.name = Some(name.to_string());
+                module.exports = exports;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn module(&self) -> Option<ModuleSpec> {
+        let top = self.top();
+        let &Environment(ref env) = &top;
+        let module = env.borrow().module.clone();
+        module
+    }
+
+    pub fn context(&self) -> Context {
+        let &Environment(ref env) = self;
+        env.borrow().context.clone()
+    }
+
+    pub fn collect_garbage(&self) -> usize {
+        let &Environment(ref env) = self;
+        let heap = env.borrow().heap.clone();
+        heap.collect()
+    }
+
+    pub fn downgrade(&self) -> WeakEnvironment {
         let &Environment(ref env) = self;
-        let enclosing = env.borrow().enclosing.clone();
-        match enclosing {
-            Some(ref e) => Environment(e.upgrade().expect("Internal error")).insert_top(key, c),
-            None        => { env.borrow_mut().table.insert(key.to_string(), c.clone()); },
+        WeakEnvironment(Rc::downgrade(env))
+    }
+
+    pub fn id(&self) -> usize {
+        let &Environment(ref env) = self;
+        &**env as *const RefCell<EnvironmentImpl> as usize
+    }
+
+    pub fn strong_count(&self) -> usize {
+        let &Environment(ref env) = self;
+        Rc::strong_count(env)
+    }
+
+    // One entry per strong reference, the collector relies on the counts matching
+    pub fn references(&self) -> Vec<Environment> {
+        let &Environment(ref env) = self;
+        let env = env.borrow();
+        let mut refs = Vec::new();
+
+        if let Some(ref e) = env.enclosing {
+            refs.push(e.clone());
+        }
+
+        for c in env.table.values().chain(env.imports.values()) {
+            gc::trace(c, &mut refs);
+        }
+
+        if let Some((_, ref values)) = env.arguments {
+            for c in values.iter() {
+                gc::trace(c, &mut refs);
+            }
+        }
+
+        refs
+    }
+
+    pub fn clear(&self) {
+        let &Environment(ref env) = self;
+        let (_table, _imports, _arguments, _enclosing) = {
+            let mut env = env.borrow_mut();
+            (mem::replace(&mut env.table, HashMap::new()),
+             mem::replace(&mut env.imports, HashMap::new()),
+             env.arguments.take(),
+             env.enclosing.take())
         };
     }
-}
\ No newline at end of file
+}
+
+impl WeakEnvironment {
+    pub fn upgrade(&self) -> Option<Environment> {
+        let &WeakEnvironment(ref env) = self;
+        env.upgrade().map(Environment)
+    }
+}
@@ -0,0 +1,89 @@
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    Parse,
+    Runtime,
+    Conversion,
+    Limit,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Frame {
+    pub name: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.name {
+            Some(ref name) => &name[..],
+            None           => "<lambda>",
+        };
+
+        match self.location {
+            Some(ref location) => write!(f, "{} ({})", name, location),
+            None               => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RlispError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub location: Option<Location>,
+    pub backtrace: Vec<Frame>,
+}
+
+impl RlispError {
+    pub fn new(kind: ErrorKind, message: String, location: Option<Location>) -> RlispError {
+        RlispError {
+            kind: kind,
+            message: message,
+            location: location,
+            backtrace: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for RlispError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error: {}", self.message)
+    }
+}
+
+impl error::Error for RlispError {
+    fn description(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+#[test]
+fn test_fmt() {
+    let e = RlispError::new(ErrorKind::Runtime, "Undefined symbol: a".to_string(), None);
+    assert_eq!(format!("{}", e), "Error: Undefined symbol: a");
+
+    let l = Location { file: "test.rl".to_string(), line: 3, column: 14 };
+    assert_eq!(format!("{}", l), "test.rl:3:14");
+
+    let frame = Frame { name: Some("f".to_string()), location: Some(l) };
+    assert_eq!(format!("{}", frame), "f (test.rl:3:14)");
+
+    let frame = Frame { name: None, location: None };
+    assert_eq!(format!("{}", frame), "<lambda>");
+}
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use cell::Cell;
+use environment::{Environment, WeakEnvironment};
+
+const MIN_COLLECT_THRESHOLD: usize = 1024;
+
+struct HeapImpl {
+    environments: Vec<WeakEnvironment>,
+    threshold:    usize,
+}
+
+// Environments own each other through Rc, so anything that is not part of a
+// cycle is freed as soon as it becomes unreachable. The collector only has to
+// find the cycles. Every reference that is not held by another environment
+// comes from the Rust side (the evaluator's stack or the host) and counts as a
+// root, everything reachable from a root is kept and the rest is cleared which
+// breaks the cycles.
+#[derive(Clone)]
+pub struct Heap(Rc<RefCell<HeapImpl>>);
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap(Rc::new(RefCell::new(HeapImpl {
+            environments: Vec::new(),
+            threshold:    MIN_COLLECT_THRESHOLD,
+        })))
+    }
+
+    pub fn register(&self, env: &Environment) {
+        let &Heap(ref heap) = self;
+        heap.borrow_mut().environments.push(env.downgrade());
+    }
+
+    pub fn should_collect(&self) -> bool {
+        let &Heap(ref heap) = self;
+        let heap = heap.borrow();
+        heap.environments.len() >= heap.threshold
+    }
+
+    pub fn collect(&self) -> usize {
+        let &Heap(ref heap) = self;
+
+        let live = {
+            let mut heap = heap.borrow_mut();
+            let live = heap.environments.iter()
+                                        .filter_map(|e| e.upgrade())
+                                        .collect::<Vec<Environment>>();
+            heap.environments = live.iter().map(|e| e.downgrade()).collect();
+            live
+        };
+
+        let mut internal: HashMap<usize, usize> = HashMap::new();
+        let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        for env in live.iter() {
+            let refs = env.references().iter().map(|r| r.id()).collect::<Vec<usize>>();
+            for id in refs.iter() {
+                *internal.entry(*id).or_insert(0) += 1;
+            }
+            edges.insert(env.id(), refs);
+        }
+
+        let mut marked: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<usize> = Vec::new();
+
+        for env in live.iter() {
+            let internal_count = internal.get(&env.id()).cloned().unwrap_or(0);
+
+            // One of the strong references is the one in live
+            if env.strong_count() - 1 > internal_count {
+                stack.push(env.id());
+            }
+        }
+
+        while let Some(id) = stack.pop() {
+            if marked.insert(id) {
+                if let Some(refs) = edges.get(&id) {
+                    stack.extend(refs.iter().cloned());
+                }
+            }
+        }
+
+        let mut freed = 0;
+
+        for env in live.iter() {
+            if !marked.contains(&env.id()) {
+                env.clear();
+                freed += 1;
+            }
+        }
+
+        heap.borrow_mut().threshold = cmp::max(MIN_COLLECT_THRESHOLD, 2 * (live.len() - freed));
+
+        freed
+    }
+}
+
+pub fn trace(c: &Cell, refs: &mut Vec<Environment>) {
+    match c {
+        &Cell::Sexpr(ref v, _) |
+        &Cell::Qexpr(ref v)               => for e in v.iter() { trace(e, refs); },
+        &Cell::CurriedBuiltin(box ref cb) => for e in cb.bound_args.iter() { trace(e, refs); },
+        &Cell::Native(box ref native)     => for e in native.bound_args.iter() { trace(e, refs); },
+        &Cell::Map(box ref m)             => for e in m.values() { trace(e, refs); },
+        &Cell::Error(box ref e)           => trace(&e.payload, refs),
+        &Cell::Lambda(box ref lambda)     |
+        &Cell::Macro(box ref lambda)      => {
+            refs.push(lambda.environment.clone());
+            for e in lambda.arguments.iter() { trace(e, refs); }
+            for e in lambda.body.iter() { trace(e, refs); }
+        },
+        _ => (),
+    }
+}
+
+#[test]
+fn test_collect_cycle() {
+    let global = Environment::new();
+    let sub_env = global.make_sub_environment();
+
+    sub_env.insert("self", &Cell::Lambda(Box::new(::cell::LambdaSpec {
+        name:           None,
+        arguments:      Vec::new(),
+        argument_types: Vec::new(),
+        body:           Vec::new(),
+        environment:    sub_env.clone(),
+        code:           None,
+    })));
+
+    assert_eq!(global.collect_garbage(), 0);
+
+    let weak = sub_env.downgrade();
+    drop(sub_env);
+
+    assert!(weak.upgrade().is_some());
+    assert_eq!(global.collect_garbage(), 1);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn test_keep_reachable() {
+    let global = Environment::new();
+    let sub_env = global.make_sub_environment();
+    sub_env.insert("a", &Cell::Integer(1));
+
+    global.insert("f", &Cell::Lambda(Box::new(::cell::LambdaSpec {
+        name:           None,
+        arguments:      Vec::new(),
+        argument_types: Vec::new(),
+        body:           Vec::new(),
+        environment:    sub_env.clone(),
+        code:           None,
+    })));
+
+    drop(sub_env);
+
+    assert_eq!(global.collect_garbage(), 0);
+
+    match global.lookup("f") {
+        Cell::Lambda(lambda) => assert_eq!(lambda.environment.lookup("a"), Cell::Integer(1)),
+        _                    => panic!("f is not a lambda"),
+    }
+}
@@ -2,170 +2,499 @@ use phf;
 
 use builtin;
 use cell::BuiltinFunctionSpec;
+use cell::Function::*;
+use types::Type;
 use types::Type::*;
 
+const KEY_T: Type = OrT(&BoolT, &OrT(&IntegerT, &OrT(&CharT, &OrT(&StringT, &SymbolT))));
+
 pub static GLOBAL_ENVIROMENT: phf::Map<&'static str, BuiltinFunctionSpec> = phf_map! {
     // Math
     "+" => BuiltinFunctionSpec {
-        func: builtin::add,
+        func: Func(builtin::add),
         name: "+",
         argument_types: &[OrT(&IntegerT, &FloatT), ElipsisT(&OrT(&IntegerT, &FloatT))],
+        accepts_errors: false,
     },
     "-" => BuiltinFunctionSpec {
-        func: builtin::sub,
+        func: Func(builtin::sub),
         name: "-",
         argument_types: &[OrT(&IntegerT, &FloatT), OptionalT(&OrT(&IntegerT, &FloatT))],
+        accepts_errors: false,
     },
     "*" => BuiltinFunctionSpec {
-        func: builtin::mul,
+        func: Func(builtin::mul),
         name: "*",
         argument_types: &[OrT(&IntegerT, &FloatT), OrT(&IntegerT, &FloatT)],
+        accepts_errors: false,
     },
     "/" => BuiltinFunctionSpec {
-        func: builtin::div,
+        func: Func(builtin::div),
         name: "/",
         argument_types: &[OrT(&IntegerT, &FloatT), OrT(&IntegerT, &FloatT)],
+        accepts_errors: false,
     },
     // sqrt
     // ^
 
     // Compare
     "==" => BuiltinFunctionSpec {
-        func: builtin::eq,
+        func: Func(builtin::eq),
         name: "==",
         argument_types: &[AnyT, AnyT],
+        accepts_errors: false,
     },
     "!=" => BuiltinFunctionSpec {
-        func: builtin::ne,
+        func: Func(builtin::ne),
         name: "!=",
         argument_types: &[AnyT, AnyT],
+        accepts_errors: false,
     },
     "<" => BuiltinFunctionSpec {
-        func: builtin::lt,
+        func: Func(builtin::lt),
         name: "<",
         argument_types: &[AnyT, AnyT],
+        accepts_errors: false,
     },
     "<=" => BuiltinFunctionSpec {
-        func: builtin::lte,
+        func: Func(builtin::lte),
         name: "<=",
         argument_types: &[AnyT, AnyT],
+        accepts_errors: false,
     },
     ">" => BuiltinFunctionSpec {
-        func: builtin::gt,
+        func: Func(builtin::gt),
         name: ">",
         argument_types: &[AnyT, AnyT],
+        accepts_errors: false,
     },
     ">=" => BuiltinFunctionSpec {
-        func: builtin::gte,
+        func: Func(builtin::gte),
         name: ">=",
         argument_types: &[AnyT, AnyT],
+        accepts_errors: false,
     },
     "and" => BuiltinFunctionSpec {
-        func: builtin::and,
+        func: Special(builtin::and),
         name: "and",
-        argument_types: &[BoolT, ElipsisT(&BoolT)],
+        argument_types: &[AnyT, ElipsisT(&AnyT)],
+        accepts_errors: false,
     },
     "or" => BuiltinFunctionSpec {
-        func: builtin::or,
+        func: Special(builtin::or),
         name: "or",
-        argument_types: &[BoolT, ElipsisT(&BoolT)],
+        argument_types: &[AnyT, ElipsisT(&AnyT)],
+        accepts_errors: false,
     },
     "not" => BuiltinFunctionSpec {
-        func: builtin::not,
+        func: Func(builtin::not),
         name: "not",
         argument_types: &[BoolT],
+        accepts_errors: false,
     },
     "if" => BuiltinFunctionSpec {
-        func: builtin::if_func,
+        func: Special(builtin::if_func),
         name: "if",
-        argument_types: &[BoolT, AnyT, AnyT],
+        argument_types: &[AnyT, AnyT, AnyT],
+        accepts_errors: false,
+    },
+    "when" => BuiltinFunctionSpec {
+        func: Special(builtin::when),
+        name: "when",
+        argument_types: &[AnyT, ElipsisT(&AnyT)],
+        accepts_errors: false,
+    },
+    "unless" => BuiltinFunctionSpec {
+        func: Special(builtin::unless),
+        name: "unless",
+        argument_types: &[AnyT, ElipsisT(&AnyT)],
+        accepts_errors: false,
+    },
+    "cond" => BuiltinFunctionSpec {
+        func: Special(builtin::cond),
+        name: "cond",
+        argument_types: &[ElipsisT(&QexprT(&[AnyT, ElipsisT(&AnyT)]))],
+        accepts_errors: false,
     },
 
     // IO
     "print" => BuiltinFunctionSpec {
-        func: builtin::print,
+        func: Func(builtin::print),
         name: "print",
         argument_types: &[ElipsisT(&AnyT)],
+        accepts_errors: false,
+    },
+    "open" => BuiltinFunctionSpec {
+        func: Func(builtin::open),
+        name: "open",
+        argument_types: &[StringT, OptionalT(&StringT)],
+        accepts_errors: false,
+    },
+    "read-line" => BuiltinFunctionSpec {
+        func: Func(builtin::read_line),
+        name: "read-line",
+        argument_types: &[FileT],
+        accepts_errors: false,
+    },
+    "read-all" => BuiltinFunctionSpec {
+        func: Func(builtin::read_all),
+        name: "read-all",
+        argument_types: &[FileT],
+        accepts_errors: false,
+    },
+    "write" => BuiltinFunctionSpec {
+        func: Func(builtin::write),
+        name: "write",
+        argument_types: &[FileT, StringT],
+        accepts_errors: false,
+    },
+    "close" => BuiltinFunctionSpec {
+        func: Func(builtin::close),
+        name: "close",
+        argument_types: &[FileT],
+        accepts_errors: false,
+    },
+    "slurp" => BuiltinFunctionSpec {
+        func: Func(builtin::slurp),
+        name: "slurp",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "spit" => BuiltinFunctionSpec {
+        func: Func(builtin::spit),
+        name: "spit",
+        argument_types: &[StringT, StringT],
+        accepts_errors: false,
     },
-    // open
-    // read
-    // write
 
     // List
     "list" => BuiltinFunctionSpec {
-        func: builtin::list,
+        func: Func(builtin::list),
         name: "list",
         argument_types: &[ElipsisT(&AnyT)],
+        accepts_errors: false,
     },
     "head" => BuiltinFunctionSpec {
-        func: builtin::head,
+        func: Func(builtin::head),
         name: "head",
         argument_types: &[QexprT(&[AnyT, ElipsisT(&AnyT)])],
+        accepts_errors: false,
     },
     "tail" => BuiltinFunctionSpec {
-        func: builtin::tail,
+        func: Func(builtin::tail),
         name: "tail",
         argument_types: &[QexprT(&[AnyT, ElipsisT(&AnyT)])],
+        accepts_errors: false,
     },
     "init" => BuiltinFunctionSpec {
-        func: builtin::init,
+        func: Func(builtin::init),
         name: "init",
         argument_types: &[QexprT(&[AnyT, ElipsisT(&AnyT)])],
+        accepts_errors: false,
     },
     "join" => BuiltinFunctionSpec {
-        func: builtin::join,
+        func: Func(builtin::join),
         name: "join",
         argument_types: &[QexprT(&[ElipsisT(&AnyT)]), ElipsisT(&QexprT(&[ElipsisT(&AnyT)]))],
+        accepts_errors: false,
     },
     "len" => BuiltinFunctionSpec {
-        func: builtin::len,
+        func: Func(builtin::len),
         name: "len",
         argument_types:  &[QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+    "nth" => BuiltinFunctionSpec {
+        func: Func(builtin::nth),
+        name: "nth",
+        argument_types: &[IntegerT, QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+
+    // Map
+    "get" => BuiltinFunctionSpec {
+        func: Func(builtin::get),
+        name: "get",
+        argument_types: &[MapT, KEY_T, OptionalT(&AnyT)],
+        accepts_errors: false,
+    },
+    "assoc" => BuiltinFunctionSpec {
+        func: Func(builtin::assoc),
+        name: "assoc",
+        argument_types: &[MapT, KEY_T, AnyT, ElipsisT(&AnyT)],
+        accepts_errors: false,
+    },
+    "dissoc" => BuiltinFunctionSpec {
+        func: Func(builtin::dissoc),
+        name: "dissoc",
+        argument_types: &[MapT, KEY_T, ElipsisT(&KEY_T)],
+        accepts_errors: false,
+    },
+    "keys" => BuiltinFunctionSpec {
+        func: Func(builtin::keys),
+        name: "keys",
+        argument_types: &[MapT],
+        accepts_errors: false,
+    },
+    "values" => BuiltinFunctionSpec {
+        func: Func(builtin::values),
+        name: "values",
+        argument_types: &[MapT],
+        accepts_errors: false,
+    },
+    "contains" => BuiltinFunctionSpec {
+        func: Func(builtin::contains),
+        name: "contains",
+        argument_types: &[MapT, KEY_T],
+        accepts_errors: false,
+    },
+    "merge" => BuiltinFunctionSpec {
+        func: Func(builtin::merge),
+        name: "merge",
+        argument_types: &[MapT, ElipsisT(&MapT)],
+        accepts_errors: false,
     },
-    // nth
 
     // String
-    // slice
-    // nth
-    // append
+    "str-len" => BuiltinFunctionSpec {
+        func: Func(builtin::str_len),
+        name: "str-len",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "str-slice" => BuiltinFunctionSpec {
+        func: Func(builtin::str_slice),
+        name: "str-slice",
+        argument_types: &[StringT, IntegerT, OptionalT(&IntegerT)],
+        accepts_errors: false,
+    },
+    "str-concat" => BuiltinFunctionSpec {
+        func: Func(builtin::str_concat),
+        name: "str-concat",
+        argument_types: &[StringT, ElipsisT(&StringT)],
+        accepts_errors: false,
+    },
+    "str-split" => BuiltinFunctionSpec {
+        func: Func(builtin::str_split),
+        name: "str-split",
+        argument_types: &[StringT, StringT],
+        accepts_errors: false,
+    },
+    "str-join" => BuiltinFunctionSpec {
+        func: Func(builtin::str_join),
+        name: "str-join",
+        argument_types: &[StringT, QexprT(&[ElipsisT(&StringT)])],
+        accepts_errors: false,
+    },
+    "str-find" => BuiltinFunctionSpec {
+        func: Func(builtin::str_find),
+        name: "str-find",
+        argument_types: &[StringT, StringT],
+        accepts_errors: false,
+    },
+    "str-replace" => BuiltinFunctionSpec {
+        func: Func(builtin::str_replace),
+        name: "str-replace",
+        argument_types: &[StringT, StringT, StringT],
+        accepts_errors: false,
+    },
+    "str-trim" => BuiltinFunctionSpec {
+        func: Func(builtin::str_trim),
+        name: "str-trim",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "str-upper" => BuiltinFunctionSpec {
+        func: Func(builtin::str_upper),
+        name: "str-upper",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "str-lower" => BuiltinFunctionSpec {
+        func: Func(builtin::str_lower),
+        name: "str-lower",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "starts-with" => BuiltinFunctionSpec {
+        func: Func(builtin::starts_with),
+        name: "starts-with",
+        argument_types: &[StringT, StringT],
+        accepts_errors: false,
+    },
+    "ends-with" => BuiltinFunctionSpec {
+        func: Func(builtin::ends_with),
+        name: "ends-with",
+        argument_types: &[StringT, StringT],
+        accepts_errors: false,
+    },
+    "str->list" => BuiltinFunctionSpec {
+        func: Func(builtin::str_to_list),
+        name: "str->list",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "list->str" => BuiltinFunctionSpec {
+        func: Func(builtin::list_to_str),
+        name: "list->str",
+        argument_types: &[QexprT(&[ElipsisT(&CharT)])],
+        accepts_errors: false,
+    },
 
     // Char
     // ??
 
     // Type
-    // type-of
-    // is-type
+    "type-of" => BuiltinFunctionSpec {
+        func: Func(builtin::type_of),
+        name: "type-of",
+        argument_types: &[AnyT],
+        accepts_errors: false,
+    },
+    "is-type" => BuiltinFunctionSpec {
+        func: Func(builtin::is_type),
+        name: "is-type",
+        argument_types: &[AnyT, TypeT],
+        accepts_errors: false,
+    },
+
+    // Error
+    "error" => BuiltinFunctionSpec {
+        func: Func(builtin::error),
+        name: "error",
+        argument_types: &[StringT, OptionalT(&AnyT)],
+        accepts_errors: false,
+    },
+    "raise" => BuiltinFunctionSpec {
+        func: Func(builtin::raise),
+        name: "raise",
+        argument_types: &[AnyT],
+        accepts_errors: false,
+    },
+    "try" => BuiltinFunctionSpec {
+        func: Special(builtin::try_func),
+        name: "try",
+        argument_types: &[AnyT, SexprT(&[SymbolT, SymbolT, ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+    "error?" => BuiltinFunctionSpec {
+        func: Func(builtin::is_error),
+        name: "error?",
+        argument_types: &[AnyT],
+        accepts_errors: true,
+    },
+    "error-message" => BuiltinFunctionSpec {
+        func: Func(builtin::error_message),
+        name: "error-message",
+        argument_types: &[ErrorT],
+        accepts_errors: true,
+    },
+    "payload" => BuiltinFunctionSpec {
+        func: Func(builtin::payload),
+        name: "payload",
+        argument_types: &[ErrorT],
+        accepts_errors: true,
+    },
 
     // Functional
-    // map
-    // fold
-    // filter
+    "map" => BuiltinFunctionSpec {
+        func: Func(builtin::map),
+        name: "map",
+        argument_types: &[OrT(&BuiltinT, &LambdaT), QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+    "filter" => BuiltinFunctionSpec {
+        func: Func(builtin::filter),
+        name: "filter",
+        argument_types: &[OrT(&BuiltinT, &LambdaT), QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+    "fold" => BuiltinFunctionSpec {
+        func: Func(builtin::fold),
+        name: "fold",
+        argument_types: &[OrT(&BuiltinT, &LambdaT), AnyT, QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
 
     // Language
     "eval" => BuiltinFunctionSpec {
-        func: builtin::eval,
+        func: TailFunc(builtin::eval),
         name: "eval",
         argument_types: &[AnyT],
+        accepts_errors: false,
+    },
+    "begin" => BuiltinFunctionSpec {
+        func: Special(builtin::begin),
+        name: "begin",
+        argument_types: &[ElipsisT(&AnyT)],
+        accepts_errors: false,
+    },
+    "let" => BuiltinFunctionSpec {
+        func: Special(builtin::let_func),
+        name: "let",
+        argument_types: &[QexprT(&[ElipsisT(&QexprT(&[SymbolT, AnyT]))]), ElipsisT(&AnyT)],
+        accepts_errors: false,
+    },
+    "let*" => BuiltinFunctionSpec {
+        func: Special(builtin::let_star),
+        name: "let*",
+        argument_types: &[QexprT(&[ElipsisT(&QexprT(&[SymbolT, AnyT]))]), ElipsisT(&AnyT)],
+        accepts_errors: false,
     },
     "def" => BuiltinFunctionSpec {
-        func: builtin::def,
+        func: Func(builtin::def),
         name: "def",
-        argument_types: &[QexprT(&[SymbolT, ElipsisT(&SymbolT)]), AnyT],
+        argument_types: &[QexprT(&[SymbolT, ElipsisT(&OrT(&SymbolT, &TypeT))]), AnyT],
+        accepts_errors: false,
     },
     "set!" => BuiltinFunctionSpec {
-        func: builtin::set,
+        func: Func(builtin::set),
         name: "set!",
         argument_types: &[QexprT(&[SymbolT]), AnyT],
+        accepts_errors: false,
     },
     "lambda" => BuiltinFunctionSpec {
-        func: builtin::lambda,
+        func: Func(builtin::lambda),
         name: "lambda",
-        argument_types: &[QexprT(&[ElipsisT(&SymbolT)]), QexprT(&[ElipsisT(&AnyT)])],
+        argument_types: &[QexprT(&[ElipsisT(&OrT(&SymbolT, &TypeT))]), QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+    "defmacro" => BuiltinFunctionSpec {
+        func: Func(builtin::defmacro),
+        name: "defmacro",
+        argument_types: &[QexprT(&[SymbolT, ElipsisT(&OrT(&SymbolT, &TypeT))]), QexprT(&[ElipsisT(&AnyT)])],
+        accepts_errors: false,
+    },
+    "macroexpand" => BuiltinFunctionSpec {
+        func: Func(builtin::macroexpand),
+        name: "macroexpand",
+        argument_types: &[AnyT],
+        accepts_errors: false,
+    },
+    "load" => BuiltinFunctionSpec {
+        func: Func(builtin::load),
+        name: "load",
+        argument_types: &[StringT],
+        accepts_errors: false,
+    },
+    "module" => BuiltinFunctionSpec {
+        func: Func(builtin::module),
+        name: "module",
+        argument_types: &[QexprT(&[SymbolT]), QexprT(&[ElipsisT(&SymbolT)])],
+        accepts_errors: false,
+    },
+    "import" => BuiltinFunctionSpec {
+        func: Func(builtin::import),
+        name: "import",
+        argument_types: &[StringT, OptionalT(&QexprT(&[ElipsisT(&SymbolT)]))],
+        accepts_errors: false,
+    },
+    "call/cc" => BuiltinFunctionSpec {
+        func: Func(builtin::call_cc),
+        name: "call/cc",
+        argument_types: &[OrT(&BuiltinT, &LambdaT)],
+        accepts_errors: false,
     },
 
-    // begin
-    // let
     // for
-    // cond
-    // continuations?
 };
\ No newline at end of file
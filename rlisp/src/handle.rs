@@ -0,0 +1,76 @@
+use cell::Cell;
+use error::{ErrorKind, RlispError};
+use super::Rlisp;
+
+// Anything Rlisp::call can resolve to a procedure
+pub trait Callable {
+    fn procedure(&self, rlisp: &Rlisp) -> Result<Cell, RlispError>;
+}
+
+// A procedure resolved once, it keeps the procedure alive even if the name is redefined
+#[derive(Clone, Debug)]
+pub struct Handle(Cell);
+
+impl Handle {
+    pub fn new(procedure: Cell) -> Result<Handle, RlispError> {
+        match procedure {
+            Cell::Builtin(_)        |
+            Cell::CurriedBuiltin(_) |
+            Cell::Native(_)         |
+            Cell::Lambda(_)         |
+            Cell::Continuation(_)   => Ok(Handle(procedure)),
+            _ => Err(RlispError::new(ErrorKind::Runtime,
+                                     format!("{} is not a procedure, is {}", procedure, procedure.get_type()),
+                                     None)),
+        }
+    }
+
+    pub fn cell(&self) -> &Cell {
+        let &Handle(ref procedure) = self;
+        procedure
+    }
+}
+
+impl<'a> Callable for &'a str {
+    fn procedure(&self, rlisp: &Rlisp) -> Result<Cell, RlispError> {
+        rlisp.lookup(self)
+    }
+}
+
+impl Callable for String {
+    fn procedure(&self, rlisp: &Rlisp) -> Result<Cell, RlispError> {
+        rlisp.lookup(self)
+    }
+}
+
+impl Callable for Cell {
+    fn procedure(&self, _: &Rlisp) -> Result<Cell, RlispError> {
+        Ok(self.clone())
+    }
+}
+
+impl<'a> Callable for &'a Cell {
+    fn procedure(&self, _: &Rlisp) -> Result<Cell, RlispError> {
+        Ok((*self).clone())
+    }
+}
+
+impl Callable for Handle {
+    fn procedure(&self, _: &Rlisp) -> Result<Cell, RlispError> {
+        Ok(self.cell().clone())
+    }
+}
+
+impl<'a> Callable for &'a Handle {
+    fn procedure(&self, _: &Rlisp) -> Result<Cell, RlispError> {
+        Ok(self.cell().clone())
+    }
+}
+
+#[test]
+fn test_handle() {
+    assert!(Handle::new(Cell::Integer(1)).is_err());
+
+    let handle = Handle::new(Cell::Continuation(1)).unwrap();
+    assert_eq!(*handle.cell(), Cell::Continuation(1));
+}
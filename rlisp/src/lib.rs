@@ -1,24 +1,50 @@
 #![feature(box_patterns)]
 #![feature(plugin)]
+#![feature(rc_counts)]
 #![feature(rc_weak)]
 #![feature(slice_patterns)]
 
 #![plugin(phf_macros)]
 
-extern crate mpc;
 extern crate phf;
 
-use cell::{Cell, CurriedBuiltinSpec, LambdaSpec};
-use environment::Environment;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+use cell::{BuiltinFunctionSpec, CurriedBuiltinSpec, FrameSpec, Function, Key, LambdaSpec};
+use cell::{NativeFunctionSpec, NativeSpec, Span, Tail};
+use context::Context;
+use environment::{Environment, ModuleSpec};
 use parser::Parser;
 
+pub use cell::Cell;
+pub use context::{Limits, THREAD_DEPTH};
+pub use convert::{FromCell, IntoArgs, IntoCell, MapKey};
+pub use error::{ErrorKind, Frame, Location, RlispError};
+pub use handle::{Callable, Handle};
+pub use types::Type;
+pub use value::Value;
+
 mod builtin;
 mod cell;
+mod compiler;
+mod context;
+mod convert;
 mod environment;
+mod error;
+mod gc;
 mod globals;
+mod handle;
+mod macros;
 mod parser;
 mod stdlib;
 mod types;
+mod value;
+mod vm;
 
 pub struct Rlisp {
     parser: Parser,
@@ -26,6 +52,7 @@ pub struct Rlisp {
 }
 
 impl Rlisp {
+    // Starts without limits, see set_limits
     pub fn new() -> Rlisp {
         let mut rlisp = Rlisp {
             parser: Parser::new(),
@@ -38,78 +65,377 @@ impl Rlisp {
     }
 
     pub fn execute(&mut self, input: &str) -> String {
-        format!("{}", eval(self.environment.clone(), &self.parser.parse(input)))
+        match self.eval_str(input) {
+            Ok(value) => format!("{}", value),
+            Err(e)    => format!("{}", e),
+        }
+    }
+
+    // Evaluates every form in the input and returns the value of the last one
+    pub fn eval_str(&mut self, input: &str) -> Result<Value, RlispError> {
+        let mut results = self.execute_all(input);
+
+        match results.pop() {
+            Some(result) => result,
+            None         => Ok(Value::Nil),
+        }
+    }
+
+    // Evaluates the forms in order and stops after the first error
+    pub fn execute_all(&mut self, input: &str) -> Vec<Result<Value, RlispError>> {
+        let exprs = match self.parser.parse_all("<input>", input) {
+            Ok(exprs) => exprs,
+            Err(e)    => return vec![Err(e)],
+        };
+
+        let mut results = Vec::new();
+
+        for expr in exprs.iter() {
+            let result = self.eval_expr(expr);
+            let is_err = result.is_err();
+
+            results.push(result);
+
+            if is_err {
+                break;
+            }
+        }
+
+        results
+    }
+
+    pub fn feed(&mut self, input: &str) -> Vec<Result<Value, RlispError>> {
+        self.parser.feed(input);
+
+        let mut results = Vec::new();
+
+        while let Some(expr) = self.parser.next("<input>") {
+            results.push(match expr {
+                Ok(expr) => self.eval_expr(&expr),
+                Err(e)   => Err(e),
+            });
+        }
+
+        results
+    }
+
+    // Evaluates what is left of the fed input once there is no more to come
+    pub fn finish(&mut self) -> Vec<Result<Value, RlispError>> {
+        let mut results = Vec::new();
+
+        while let Some(expr) = self.parser.finish("<input>") {
+            results.push(match expr {
+                Ok(expr) => self.eval_expr(&expr),
+                Err(e)   => Err(e),
+            });
+        }
+
+        results
+    }
+
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Value, RlispError> {
+        self.environment.context().start();
+        let result = try!(load(self.environment.clone(), path.as_ref()));
+        self.to_result(result)
+    }
+
+    // Calls a procedure by name, as a cell or through a handle with already evaluated arguments
+    pub fn call<C: Callable, A: IntoArgs, R: FromCell>(&mut self, procedure: C, args: A) -> Result<R, RlispError> {
+        let procedure = try!(procedure.procedure(self));
+        self.environment.context().start();
+        let result = call_procedure(self.environment.clone(), &procedure, args.into_args());
+        R::from_cell(&try!(self.to_cell(result)))
+    }
+
+    // Resolves the procedure once so it can be called repeatedly
+    pub fn handle<C: Callable>(&self, procedure: C) -> Result<Handle, RlispError> {
+        let procedure = try!(procedure.procedure(self));
+        Handle::new(procedure)
+    }
+
+    pub fn get<T: FromCell>(&self, name: &str) -> Result<T, RlispError> {
+        T::from_cell(&try!(self.lookup(name)))
+    }
+
+    // Only the definitions made at the top level, not the builtins
+    pub fn get_global(&self, name: &str) -> Option<Cell> {
+        self.environment.get(name)
+    }
+
+    pub fn set_global<T: IntoCell>(&mut self, name: &str, value: T) {
+        self.environment.insert(name, &value.into_cell());
+    }
+
+    pub fn remove_global(&mut self, name: &str) -> Option<Cell> {
+        self.environment.remove(name)
+    }
+
+    // The function is checked and curried like the builtins in globals.rs, it
+    // gets the arguments as Values and an Err result is raised as an error
+    pub fn register_fn<R, F>(&mut self, name: &str, argument_types: Vec<Type>, func: F) -> Result<(), RlispError>
+        where R: IntoCell, F: Fn(&[Value]) -> R + 'static
+    {
+        if let Err(e) = types::check_arity(&argument_types[..]) {
+            return Err(RlispError::new(ErrorKind::Runtime, format!("register_fn, {}: {}", name, e), None));
+        }
+
+        self.environment.insert(name, &Cell::Native(Box::new(NativeSpec {
+            function: Rc::new(NativeFunctionSpec {
+                func:           Box::new(move |args: &[Cell]| {
+                    let args = args.iter().map(Value::from).collect::<Vec<Value>>();
+                    func(&args[..]).into_cell()
+                }),
+                name:           Rc::new(name.to_string()),
+                argument_types: argument_types,
+            }),
+            bound_args: Vec::new(),
+        })));
+
+        Ok(())
+    }
+
+    // Exceeding a limit stops the evaluation with an error of kind Limit
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.environment.context().set_limits(limits);
+    }
+
+    // Lambdas defined while this is off run in the tree walking evaluator
+    pub fn set_compile(&mut self, compile: bool) {
+        self.environment.context().set_compile(compile);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.parser.is_pending()
+    }
+
+    pub fn collect_garbage(&mut self) -> usize {
+        self.environment.collect_garbage()
+    }
+
+    fn eval_expr(&mut self, expr: &Cell) -> Result<Value, RlispError> {
+        self.environment.context().start();
+        let result = eval(self.environment.clone(), expr);
+        self.to_result(result)
+    }
+
+    fn to_result(&self, result: Cell) -> Result<Value, RlispError> {
+        let result = try!(self.to_cell(result));
+        Ok(Value::from(&result))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Cell, RlispError> {
+        self.to_cell(self.environment.lookup(name))
+    }
+
+    fn to_cell(&self, result: Cell) -> Result<Cell, RlispError> {
+        let kind = if result.is_limit() { ErrorKind::Limit } else { ErrorKind::Runtime };
+
+        match result {
+            Cell::Error(box e) => {
+                let mut error = RlispError::new(kind, e.message,
+                                                e.location.map(|l| (*l).clone()));
+
+                error.backtrace = e.backtrace.into_iter().map(|f| Frame {
+                    name:     f.name.map(|n| (*n).clone()),
+                    location: f.location.map(|l| (*l).clone()),
+                }).collect();
+
+                Err(error)
+            },
+            result => Ok(result),
+        }
     }
 }
 
-fn apply(env: Environment, procedure: &Cell, args: &[Cell]) -> Cell {
+impl Drop for Rlisp {
+    fn drop(&mut self) {
+        self.environment.context().clear();
+        self.environment.clear();
+        self.environment.collect_garbage();
+    }
+}
+
+fn load(env: Environment, path: &Path) -> Result<Cell, RlispError> {
+    let context = env.context();
+
+    let path = match context.enter(&context.resolve(path)) {
+        Ok(path) => path,
+        Err(e)   => return Err(RlispError::new(ErrorKind::Runtime, format!("load, {}", e), None)),
+    };
+
+    let result = load_entered("load", env.top(), &path);
+    context.leave();
+    result
+}
+
+fn import(env: Environment, path: &Path) -> Result<(String, Cell), Cell> {
+    let context = env.context();
+
+    let path = match context.enter(&context.resolve(path)) {
+        Ok(path) => path,
+        Err(e)   => return Err(Cell::error(format!("import, {}", e))),
+    };
+
+    if let Some(module) = context.module(&path) {
+        context.leave();
+        return Ok(module);
+    }
+
+    let module_env = env.root().make_module_environment();
+    let result = load_entered("import", module_env.clone(), &path);
+    context.leave();
+
+    match result {
+        Ok(e @ Cell::Error(_)) => return Err(e),
+        Ok(_)                  => (),
+        Err(e)                 => return Err(Cell::from_error(e)),
+    }
+
+    let (name, names) = match module_env.module() {
+        Some(ModuleSpec { name: Some(name), exports }) => (name, exports),
+        _ => return Err(Cell::error(format!("import, {} does not declare a module", path.display()))),
+    };
+
+    let mut exports = BTreeMap::new();
+
+    // Only the module's own definitions, not the globals of the importer
+    for export in names.iter() {
+        match module_env.get_local(export) {
+            Some(c) => { exports.insert(Key::Symbol(export.clone()), c); },
+            None    => return Err(Cell::error(format!("import, module {} does not define {}", name, export))),
+        }
+    }
+
+    let module = Cell::Map(Box::new(exports));
+    context.insert_module(path, name.clone(), module.clone());
+
+    Ok((name, module))
+}
+
+fn load_entered(name: &str, env: Environment, path: &Path) -> Result<Cell, RlispError> {
+    let mut source = String::new();
+
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut source)) {
+        return Err(RlispError::new(ErrorKind::Runtime, format!("{}, {}: {}", name, path.display(), e), None));
+    }
+
+    let exprs = try!(Parser::new().parse_all(&path.to_string_lossy(), &source[..]));
+    let mut result = Cell::Nil;
+
+    for expr in exprs.iter() {
+        result = eval(env.clone(), expr);
+
+        if let Cell::Error(_) = result {
+            break;
+        }
+    }
+
+    Ok(result)
+}
+
+fn apply(env: Environment, procedure: &Cell, args: &[Cell], location: &Span) -> Tail {
     if let Some(e) = first_error(args) {
-         return e.clone();
+         return Tail::Return(e.clone());
     }
     
     let evaled_args = args.iter().map(|a| eval(env.clone(), a)).collect::<Vec<Cell>>();
-    let evaled_args_len = evaled_args.len();
 
-    if let Some(e) = first_error(&evaled_args[..]) {
-         return e.clone();
+    if let Some(e) = argument_error(procedure, &evaled_args[..]) {
+         return Tail::Return(e.clone());
     }
 
+    call(env, procedure, evaled_args, location)
+}
+
+fn call(env: Environment, procedure: &Cell, evaled_args: Vec<Cell>, location: &Span) -> Tail {
+    let evaled_args_len = evaled_args.len();
+
     match procedure {
         &Cell::Lambda(box ref lambda) => {
-            let sub_env = lambda.environment.make_sub_environment();
+            let name = match lambda.name {
+                Some(ref name) => &name[..],
+                None           => "lambda",
+            };
 
-            let mut found_elipsis = false;
+            let arity = types::get_arity(&lambda.argument_types[..]);
 
-            for (i, arg) in evaled_args.iter().enumerate() {
-                if let Some(&Cell::Symbol(ref s)) = lambda.arguments.get(i) {
-                    match (&s[..] == "...", i == lambda.arguments.len() - 1) {
-                        (true, true)  => { sub_env.insert(s, &Cell::Qexpr(evaled_args[i..].to_vec())); found_elipsis = true; break; },
-                        (true, false) => return Cell::Error("Lambda can only have ... as it's last argument".to_string()),
-                        (false, _)    => sub_env.insert(s, arg),
-                    }
+            if evaled_args_len as i32 >= arity.requierd {
+                if let Some(e) = types::validate_arguments(name, &lambda.argument_types[..], &evaled_args[..]) {
+                    return Tail::Return(Cell::error(e));
                 }
-            }
 
-            if evaled_args_len == 0 {
-                if let Some(&Cell::Symbol(ref s)) = lambda.arguments.get(0) {
-                    if &s[..] == "..." {
-                        sub_env.insert(s, &Cell::Qexpr(Vec::new()));
-                        found_elipsis = true;
-                    }
+                let mut values = Vec::with_capacity(lambda.arguments.len());
+
+                for (i, t) in lambda.argument_types.iter().enumerate() {
+                    values.push(if t.is_elipsis() {
+                        Cell::Qexpr(evaled_args[cmp::min(i, evaled_args_len)..].to_vec())
+                    } else {
+                        evaled_args.get(i).cloned().unwrap_or(Cell::Nil)
+                    });
                 }
-            }
 
-            if evaled_args_len == lambda.arguments.len() || found_elipsis {
-                eval(sub_env.clone(), &Cell::Sexpr(lambda.body.clone()))
-            } else if evaled_args_len < lambda.arguments.len() {
-                if evaled_args_len == 0 {
-                    Cell::Error(format!("{} got no arguments", procedure))
-                } else {
-                    Cell::Lambda(Box::new(LambdaSpec {
-                        arguments:   lambda.arguments[evaled_args_len..].to_vec(),
-                        body:        lambda.body.clone(),
-                        environment: sub_env.clone(),
-                    }))
+                // Compiled code reads the arguments by position, they only go in the table if they are removed
+                match lambda.code {
+                    Some(ref code) => {
+                        let sub_env = lambda.environment.make_argument_environment(code.arguments.clone(), values);
+                        Tail::Run(lambda.name.clone(), sub_env, code.clone())
+                    },
+                    None => {
+                        let sub_env = lambda.environment.make_sub_environment();
+
+                        for (arg, value) in lambda.arguments.iter().zip(values.iter()) {
+                            if let &Cell::Symbol(ref s) = arg {
+                                sub_env.insert(s, value);
+                            }
+                        }
+
+                        Tail::Call(lambda.name.clone(), sub_env, Cell::Sexpr(lambda.body.clone(), None))
+                    },
                 }
+            } else if evaled_args_len == 0 {
+                Tail::Return(Cell::error(format!("{} got no arguments", procedure)))
             } else {
-                Cell::Error(format!("{} got to many arguments expected {} got {}",
-                                    procedure, lambda.arguments.len(), evaled_args_len))
+                if let Some(e) = types::validate_arguments(name, &lambda.argument_types[..evaled_args_len], &evaled_args[..]) {
+                    return Tail::Return(Cell::error(e));
+                }
+
+                let sub_env = lambda.environment.make_sub_environment();
+
+                for (arg, evaled_arg) in lambda.arguments.iter().zip(evaled_args.iter()) {
+                    if let &Cell::Symbol(ref s) = arg {
+                        sub_env.insert(s, evaled_arg);
+                    }
+                }
+
+                let code = match lambda.code {
+                    Some(ref code) => compiler::partial(code, &sub_env, &lambda.arguments[..], &lambda.body[..], evaled_args_len),
+                    None           => None,
+                };
+
+                Tail::Return(Cell::Lambda(Box::new(LambdaSpec {
+                    name:           lambda.name.clone(),
+                    code:           code,
+                    arguments:      lambda.arguments[evaled_args_len..].to_vec(),
+                    argument_types: lambda.argument_types[evaled_args_len..].to_vec(),
+                    body:           lambda.body.clone(),
+                    environment:    sub_env,
+                })))
             }
         },
+        // The arguments are already evaluated, a special form would evaluate them again
+        &Cell::Builtin(builtin) if builtin.is_special() => {
+            Tail::Return(builtin_frame(builtin, Cell::error(format!("{}, special form can't be applied", builtin.name)), location))
+        },
         &Cell::Builtin(builtin) => {
             let arity = types::get_arity(builtin.argument_types);
 
             if evaled_args_len as i32 >= arity.requierd {
-                if let Some(e) = types::validate(builtin, &evaled_args[..]) {
-                    return Cell::Error(e);
-                }
-
-                (builtin.func)(env, &evaled_args[..])
+                call_builtin(builtin, env, &evaled_args[..], location)
             } else {
-                Cell::CurriedBuiltin(Box::new(CurriedBuiltinSpec {
+                Tail::Return(Cell::CurriedBuiltin(Box::new(CurriedBuiltinSpec {
                     builtin: builtin,
                     bound_args: evaled_args,
-                }))
+                })))
             }            
         },
         &Cell::CurriedBuiltin(box ref cb) => {
@@ -119,43 +445,247 @@ fn apply(env: Environment, procedure: &Cell, args: &[Cell]) -> Cell {
             evaled_and_bound_args.extend(evaled_args);
 
             if evaled_and_bound_args.len() as i32 >= arity.requierd {
-                if let Some(e) = types::validate(cb.builtin, &evaled_and_bound_args[..]) {
-                    return Cell::Error(e);
-                }
-
-                (cb.builtin.func)(env, &evaled_and_bound_args[..])
+                call_builtin(cb.builtin, env, &evaled_and_bound_args[..], location)
             } else if evaled_args_len == 0 {
-                Cell::Error(format!("{} got no arguments", procedure))
+                Tail::Return(Cell::error(format!("{} got no arguments", procedure)))
             } else {
-                Cell::CurriedBuiltin(Box::new(CurriedBuiltinSpec {
+                Tail::Return(Cell::CurriedBuiltin(Box::new(CurriedBuiltinSpec {
                     builtin: cb.builtin,
                     bound_args: evaled_and_bound_args,
-                }))
+                })))
             }            
         },
-        _ => Cell::Error(format!("{} is not a procedure, is {}", *procedure, procedure.get_type())),
+        &Cell::Native(box ref native) => {
+            let arity = types::get_arity(&native.function.argument_types[..]);
+            let bound_args_len = native.bound_args.len();
+            let mut evaled_and_bound_args = native.bound_args.clone();
+            evaled_and_bound_args.extend(evaled_args);
+
+            if evaled_and_bound_args.len() as i32 >= arity.requierd {
+                call_native(&native.function, &evaled_and_bound_args[..], location)
+            } else if evaled_args_len == 0 && bound_args_len > 0 {
+                Tail::Return(Cell::error(format!("{} got no arguments", procedure)))
+            } else {
+                Tail::Return(Cell::Native(Box::new(NativeSpec {
+                    function: native.function.clone(),
+                    bound_args: evaled_and_bound_args,
+                })))
+            }
+        },
+        &Cell::Continuation(id) => {
+            let value = match &evaled_args[..] {
+                []      => Cell::Nil,
+                [ref v] => v.clone(),
+                _       => return Tail::Return(Cell::error(format!("continuation, requiers 0 or 1 arguments, got {}", evaled_args_len))),
+            };
+
+            if env.context().is_active_continuation(id) {
+                Tail::Return(Cell::escape(id, value))
+            } else {
+                Tail::Return(Cell::error("continuation can't be resumed after its call/cc has returned".to_string()))
+            }
+        },
+        _ => Tail::Return(Cell::error(format!("{} is not a procedure, is {}", *procedure, procedure.get_type()))),
+    }
+}
+
+fn call_procedure(env: Environment, procedure: &Cell, args: Vec<Cell>) -> Cell {
+    let tail = call(env, procedure, args, &None);
+    force(tail, None)
+}
+
+fn expand(spec: &LambdaSpec, forms: &[Cell], location: &Span) -> Cell {
+    let arity = types::get_arity(&spec.argument_types[..]);
+
+    if (forms.len() as i32) < arity.requierd {
+        let name = match spec.name {
+            Some(ref name) => &name[..],
+            None           => "macro",
+        };
+
+        return Cell::error(format!("{}, requiers {} arguments, got {}", name, arity.requierd, forms.len()));
     }
+
+    macros::instantiate(spec, forms, location)
+}
+
+fn call_builtin(builtin: &BuiltinFunctionSpec, env: Environment, args: &[Cell], location: &Span) -> Tail {
+    if let Some(e) = types::validate(builtin, args) {
+        return Tail::Return(builtin_frame(builtin, Cell::error(e), location));
+    }
+
+    let tail = match &builtin.func {
+        &Function::Func(func)     => Tail::Return(func(env, args)),
+        &Function::TailFunc(func) => func(env, args),
+        // Errors from evaluating the forms already carry their own frames
+        &Function::Special(func)  => return func(env, args),
+    };
+
+    match tail {
+        Tail::Return(c @ Cell::Error(_)) => Tail::Return(builtin_frame(builtin, c, location)),
+        tail                             => tail,
+    }
+}
+
+fn call_native(native: &NativeFunctionSpec, args: &[Cell], location: &Span) -> Tail {
+    let result = match types::validate_arguments(&native.name, &native.argument_types[..], args) {
+        Some(e) => Cell::error(e),
+        None    => (native.func)(args),
+    };
+
+    match result {
+        c @ Cell::Error(_) => Tail::Return(push_frame(c, FrameSpec {
+            name:     Some(native.name.clone()),
+            location: location.clone(),
+        })),
+        c => Tail::Return(c),
+    }
+}
+
+fn builtin_frame(builtin: &BuiltinFunctionSpec, c: Cell, location: &Span) -> Cell {
+    push_frame(c, FrameSpec {
+        name:     Some(Rc::new(builtin.name.to_string())),
+        location: location.clone(),
+    })
 }
 
 fn eval(env: Environment, c: &Cell) -> Cell {
+    let location = match c {
+        &Cell::Sexpr(_, ref location) => location.clone(),
+        _                             => None,
+    };
+
+    let tail = eval_step(env, c, &location);
+    force(tail, location)
+}
+
+fn eval_step(env: Environment, c: &Cell, location: &Span) -> Tail {
     match c {
-        &Cell::Sexpr(ref v) => match &v[..] {
+        &Cell::Sexpr(ref v, _) => match &v[..] {
             [ref procedure, args..] => {
                 let evaled_procedure = eval(env.clone(), procedure);
+                dispatch(env, &evaled_procedure, args, location)
+            },
+            _ => Tail::Return(c.clone()),
+        },
+        &Cell::Symbol(ref s) => Tail::Return(env.lookup(s)),
+        _ => Tail::Return(c.clone()),
+    }
+}
+
+fn dispatch(env: Environment, procedure: &Cell, args: &[Cell], location: &Span) -> Tail {
+    match special_form(env.clone(), procedure, args, location) {
+        Some(tail) => tail,
+        None       => apply(env, procedure, args, location),
+    }
+}
+
+// Procedures that get their arguments unevaluated, errors stop the evaluation
+fn special_form(env: Environment, procedure: &Cell, args: &[Cell], location: &Span) -> Option<Tail> {
+    match procedure {
+        &Cell::Error(_) => Some(Tail::Return(procedure.clone())),
+        &Cell::Builtin(builtin) => match builtin.func {
+            Function::Special(_) => Some(call_builtin(builtin, env, args, location)),
+            _                    => None,
+        },
+        &Cell::Macro(box ref spec) => Some(match expand(spec, args, location) {
+            c @ Cell::Error(_) => Tail::Return(c),
+            c                  => Tail::Eval(env, c),
+        }),
+        _ => None,
+    }
+}
+
+fn force(tail: Tail, location: Span) -> Cell {
+    let context = match tail {
+        Tail::Return(c)             => return locate(c, location),
+        Tail::Eval(ref env, _)      |
+        Tail::Call(_, ref env, _)   |
+        Tail::Run(_, ref env, _)    => env.context(),
+    };
+
+    if let Some(e) = context.enter_call() {
+        return locate(e, location);
+    }
+
+    let c = force_steps(&context, tail, location);
+    context.leave_call();
+    c
+}
+
+fn force_steps(context: &Context, tail: Tail, location: Span) -> Cell {
+    let mut tail = tail;
+    let mut location = location;
+    let mut frame = None;
+
+    loop {
+        let exceeded = match tail {
+            Tail::Return(_) => None,
+            _               => context.step(),
+        };
 
-                if let Cell::Error(_) = evaled_procedure {
-                    return evaled_procedure.clone();
+        if let Some(e) = exceeded {
+            tail = Tail::Return(e);
+        }
+
+        tail = match tail {
+            Tail::Return(c) => {
+                let c = locate(c, location);
+
+                return match frame {
+                    Some(frame) => push_frame(c, frame),
+                    None        => c,
+                };
+            },
+            Tail::Eval(env, c) => {
+                if let Cell::Sexpr(_, Some(ref l)) = c {
+                    location = Some(l.clone());
                 }
 
-                apply(env.clone(), &evaled_procedure, args)
+                eval_step(env, &c, &location)
             },
-            _ => c.clone(),
-        },
-        &Cell::Symbol(ref s) => env.lookup(s),
-        _ => c.clone(),
+            Tail::Call(name, env, c) => {
+                frame = Some(FrameSpec {
+                    name:     name,
+                    location: location.clone(),
+                });
+
+                eval_step(env, &c, &location)
+            },
+            Tail::Run(name, env, code) => {
+                frame = Some(FrameSpec {
+                    name:     name,
+                    location: location.clone(),
+                });
+
+                vm::run(env, &code, &mut location)
+            },
+        };
     }
 }
 
+fn locate(c: Cell, location: Span) -> Cell {
+    let mut c = c;
+
+    if let Cell::Error(ref mut e) = c {
+        if e.location.is_none() {
+            e.location = location;
+        }
+    }
+
+    c
+}
+
+fn push_frame(c: Cell, frame: FrameSpec) -> Cell {
+    let mut c = c;
+
+    if let Cell::Error(ref mut e) = c {
+        e.backtrace.push(frame);
+    }
+
+    c
+}
+
 fn first_error(c: &[Cell]) -> Option<&Cell> {
     for e in c.iter() {
         if let &Cell::Error(_) = e {
@@ -163,4 +693,19 @@ fn first_error(c: &[Cell]) -> Option<&Cell> {
         }
     }
     None
+}
+
+// Builtins flagged to accept errors get them as values, escapes still stop them
+fn argument_error<'a>(procedure: &Cell, args: &'a [Cell]) -> Option<&'a Cell> {
+    let accepts_errors = match procedure {
+        &Cell::Builtin(builtin)           => builtin.accepts_errors,
+        &Cell::CurriedBuiltin(box ref cb) => cb.builtin.accepts_errors,
+        _                                 => false,
+    };
+
+    if accepts_errors {
+        args.iter().find(|c| c.is_escape())
+    } else {
+        first_error(args)
+    }
 }
\ No newline at end of file
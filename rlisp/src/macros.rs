@@ -0,0 +1,209 @@
+use std::cmp;
+use std::collections::HashMap;
+
+use cell::{Cell, LambdaSpec, Span};
+use environment::Environment;
+
+#[cfg(test)]
+use std::rc::Rc;
+#[cfg(test)]
+use types;
+
+enum Binding {
+    Form(Cell),
+    Splice(Vec<Cell>),
+}
+
+struct Template<'a> {
+    environment: &'a Environment,
+    bindings: HashMap<String, Binding>,
+}
+
+type Renames = HashMap<String, Cell>;
+
+// Macros are templates, the body is never evaluated at expansion time. The
+// arguments are replaced by the forms at the call site, the names the
+// template binds itself are renamed inside their scope so they can't capture
+// the callers variables, and builtins named by the template are embedded so
+// shadowing them at the call site doesn't change the expansion.
+pub fn instantiate(spec: &LambdaSpec, forms: &[Cell], location: &Span) -> Cell {
+    let mut bindings = HashMap::new();
+
+    for (i, (arg, t)) in spec.arguments.iter().zip(spec.argument_types.iter()).enumerate() {
+        if let &Cell::Symbol(ref s) = arg {
+            let binding = if t.is_elipsis() {
+                Binding::Splice(forms[cmp::min(i, forms.len())..].to_vec())
+            } else {
+                Binding::Form(forms.get(i).cloned().unwrap_or(Cell::Nil))
+            };
+
+            bindings.insert(s.clone(), binding);
+        }
+    }
+
+    let template = Template {
+        environment: &spec.environment,
+        bindings: bindings,
+    };
+
+    Cell::Sexpr(template.list(&spec.body, &HashMap::new()), location.clone())
+}
+
+impl<'a> Template<'a> {
+    // Lambda, let, let*, the arguments of a function def and catch open a scope
+    fn list(&self, v: &[Cell], renames: &Renames) -> Vec<Cell> {
+        let mut result = Vec::with_capacity(v.len());
+
+        match v {
+            [Cell::Symbol(ref s), Cell::Qexpr(ref arguments), ..] if &s[..] == "lambda" => {
+                let scope = self.scope(arguments, renames);
+                self.push(&mut result, &v[0], true, renames);
+                self.push_all(&mut result, &v[1..], &scope);
+            },
+            [Cell::Symbol(ref s), Cell::Qexpr(ref signature), ..] if &s[..] == "def" && signature.len() > 1 => {
+                let scope = self.scope(&signature[1..], renames);
+                let mut names = Vec::with_capacity(signature.len());
+                self.push(&mut names, &signature[0], false, renames);
+                self.push_all(&mut names, &signature[1..], &scope);
+
+                self.push(&mut result, &v[0], true, renames);
+                result.push(Cell::Qexpr(names));
+                self.push_all(&mut result, &v[2..], &scope);
+            },
+            [Cell::Symbol(ref s), Cell::Qexpr(ref bindings), ..] if &s[..] == "let" || &s[..] == "let*" => {
+                let sequential = &s[..] == "let*";
+                let mut scope = renames.clone();
+                let mut list = Vec::with_capacity(bindings.len());
+
+                // The values of let are in the outer scope, let* also sees the earlier names
+                for binding in bindings.iter() {
+                    match binding {
+                        &Cell::Qexpr(ref binding) if !binding.is_empty() => {
+                            let outer = if sequential { scope.clone() } else { renames.clone() };
+                            scope = self.scope(&binding[..1], &scope);
+
+                            let mut pair = Vec::with_capacity(binding.len());
+                            self.push(&mut pair, &binding[0], false, &scope);
+                            self.push_all(&mut pair, &binding[1..], &outer);
+                            list.push(Cell::Qexpr(pair));
+                        },
+                        c => self.push(&mut list, c, false, renames),
+                    }
+                }
+
+                self.push(&mut result, &v[0], true, renames);
+                result.push(Cell::Qexpr(list));
+                self.push_all(&mut result, &v[2..], &scope);
+            },
+            [Cell::Symbol(ref s), Cell::Symbol(_), ..] if &s[..] == "catch" => {
+                let scope = self.scope(&v[1..2], renames);
+                self.push(&mut result, &v[0], true, renames);
+                self.push_all(&mut result, &v[1..], &scope);
+            },
+            _ => {
+                for (i, c) in v.iter().enumerate() {
+                    self.push(&mut result, c, i == 0, renames);
+                }
+            },
+        }
+
+        result
+    }
+
+    // A fresh name for every symbol bound here, the macro arguments are the callers forms
+    fn scope(&self, names: &[Cell], renames: &Renames) -> Renames {
+        let context = self.environment.context();
+        let mut scope = renames.clone();
+
+        for c in names.iter() {
+            match c {
+                &Cell::Symbol(ref s) if &s[..] != "..." && !self.bindings.contains_key(s) => {
+                    scope.insert(s.clone(), Cell::Symbol(format!("{}#{}", s, context.next_symbol())));
+                },
+                _ => (),
+            }
+        }
+
+        scope
+    }
+
+    fn push_all(&self, result: &mut Vec<Cell>, v: &[Cell], renames: &Renames) {
+        for c in v.iter() {
+            self.push(result, c, false, renames);
+        }
+    }
+
+    fn push(&self, result: &mut Vec<Cell>, c: &Cell, head: bool, renames: &Renames) {
+        match c {
+            &Cell::Symbol(ref s) => match self.bindings.get(s) {
+                Some(&Binding::Form(ref form))   => result.push(form.clone()),
+                Some(&Binding::Splice(ref forms)) => result.extend(forms.iter().cloned()),
+                None                             => result.push(self.symbol(s, head, renames)),
+            },
+            &Cell::Sexpr(ref v, ref span) => result.push(Cell::Sexpr(self.list(v, renames), span.clone())),
+            &Cell::Qexpr(ref v)           => result.push(Cell::Qexpr(self.list(v, renames))),
+            c                             => result.push(c.clone()),
+        }
+    }
+
+    fn symbol(&self, s: &str, head: bool, renames: &Renames) -> Cell {
+        if let Some(renamed) = renames.get(s) {
+            return renamed.clone();
+        }
+
+        if head {
+            if let c @ Cell::Builtin(_) = self.environment.lookup(s) {
+                return c;
+            }
+        }
+
+        Cell::Symbol(s.to_string())
+    }
+}
+
+#[test]
+fn test_instantiate() {
+    let env = Environment::new();
+    let sym = |s: &str| Cell::Symbol(s.to_string());
+
+    // (defmacro {my-or a b} {let {{tmp a}} (if tmp tmp b)})
+    let spec = LambdaSpec {
+        name:           Some(Rc::new("my-or".to_string())),
+        arguments:      vec![sym("a"), sym("b")],
+        argument_types: vec![types::Type::AnyT, types::Type::AnyT],
+        body:           vec![sym("let"),
+                             Cell::Qexpr(vec![Cell::Qexpr(vec![sym("tmp"), sym("a")])]),
+                             Cell::Sexpr(vec![sym("if"), sym("tmp"), sym("tmp"), sym("b")], None)],
+        environment:    env.clone(),
+        code:           None,
+    };
+
+    let expansion = instantiate(&spec, &[Cell::Bool(false), sym("tmp")], &None);
+
+    assert_eq!(expansion, Cell::Sexpr(vec![env.lookup("let"),
+                                           Cell::Qexpr(vec![Cell::Qexpr(vec![sym("tmp#1"), Cell::Bool(false)])]),
+                                           Cell::Sexpr(vec![env.lookup("if"), sym("tmp#1"), sym("tmp#1"), sym("tmp")], None)],
+                                      None));
+
+    // (defmacro {shadowed v} {list x (let {{x v}} x)}), only the x inside the let is renamed
+    let spec = LambdaSpec {
+        name:           Some(Rc::new("shadowed".to_string())),
+        arguments:      vec![sym("v")],
+        argument_types: vec![types::Type::AnyT],
+        body:           vec![sym("list"),
+                             sym("x"),
+                             Cell::Sexpr(vec![sym("let"), Cell::Qexpr(vec![Cell::Qexpr(vec![sym("x"), sym("v")])]), sym("x")], None)],
+        environment:    env.clone(),
+        code:           None,
+    };
+
+    let expansion = instantiate(&spec, &[sym("x")], &None);
+
+    assert_eq!(expansion, Cell::Sexpr(vec![env.lookup("list"),
+                                           sym("x"),
+                                           Cell::Sexpr(vec![env.lookup("let"),
+                                                            Cell::Qexpr(vec![Cell::Qexpr(vec![sym("x#2"), sym("x")])]),
+                                                            sym("x#2")],
+                                                       None)],
+                                      None));
+}
@@ -1,173 +1,778 @@
-use cell::Cell;
-use mpc;
+use std::char;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
-pub struct Parser {
-    float:   mpc::Parser,
-    integer: mpc::Parser,
-    string:  mpc::Parser,
-    char:    mpc::Parser,
-    bool:    mpc::Parser,
-    symbol:  mpc::Parser,
-    comment: mpc::Parser,
-    expr:    mpc::Parser,
-    sexpr:   mpc::Parser,
-    qexpr:   mpc::Parser,
-    rlisp:   mpc::Parser,
-}
+use cell::{Cell, Key};
+use error::{ErrorKind, Location, RlispError};
+use types::{self, Type};
+use types::Type::*;
 
-impl Parser {
-    pub fn new() -> Parser {
-        let mut parser = Parser {
-            float:   mpc::Parser::new("float"),
-            integer: mpc::Parser::new("integer"),
-            string:  mpc::Parser::new("string"),
-            char:    mpc::Parser::new("char"),
-            bool:    mpc::Parser::new("bool"),
-            symbol:  mpc::Parser::new("symbol"),
-            comment: mpc::Parser::new("comment"),
-            expr:    mpc::Parser::new("expr"),
-            sexpr:   mpc::Parser::new("sexpr"),
-            qexpr:   mpc::Parser::new("qexpr"),
-            rlisp:   mpc::Parser::new("rlisp"),
-        };
+enum ReadError {
+    Incomplete(RlispError),
+    Invalid(RlispError),
+}
 
-        if let Some(e) = mpc::lang(mpc::DEFAULT, r###"
-                 float   : /[-+]?[0-9]*\\.?[0-9]+([eE][-+]?[0-9]+)?/;
-                 integer : /[-+]?[0-9]+/;
-                 string  : /\"[^\"]*\"/;
-                 char    : /\'[^\']\'/;
-                 bool    : /#t/ | /#f/;
-                 symbol  : /[a-zA-Z!$%&\*\+\-\.\/:<=>\?@^_~\\][0-9a-zA-Z!$%&\*\+\-\.\/:<=>\?@^_~\\]*/;
-                 comment : /;[^\r\n]*/;
-                 expr    : <float>   | <integer> | <string> | <char> | <bool> | <symbol> |
-                           <comment> | <sexpr>   | <qexpr>;
-                 sexpr   : '(' <expr>* ')';
-                 qexpr   : '{' <expr>* '}';
-                 rlisp   : /^/ <expr>* /$/;
-                 "###,
-                &[&mut parser.float,
-                  &mut parser.integer,
-                  &mut parser.string,
-                  &mut parser.char,
-                  &mut parser.bool,
-                  &mut parser.symbol,
-                  &mut parser.comment,
-                  &mut parser.expr,
-                  &mut parser.sexpr,
-                  &mut parser.qexpr,
-                  &mut parser.rlisp]) {
-
-            let error = e.to_string();
-            panic!("{}", error);
-        }
-
-        parser
-    }
-
-    pub fn parse(&self, input: &str) -> Cell {
-        let ast = match self.rlisp.parse(input) {
-            Some(mpc::Result::Ast(a))   => a,
-            Some(mpc::Result::Error(e)) => { return Cell::Error(e.to_string()); },
-            None                        => { panic!("Internal parsing error") },
-        };
-        
-        match parse_ast(&ast) {
-            Some(cell) => cell,
-            None       => Cell::Nil,
+impl ReadError {
+    fn into_error(self) -> RlispError {
+        match self {
+            ReadError::Incomplete(e) => e,
+            ReadError::Invalid(e)    => e,
         }
     }
 }
 
-impl Drop for Parser {
-    fn drop(&mut self) {
-          mpc::cleanup(&[&mut self.float,
-                         &mut self.integer,
-                         &mut self.string,
-                         &mut self.char,
-                         &mut self.bool,
-                         &mut self.symbol,
-                         &mut self.comment,
-                         &mut self.expr,
-                         &mut self.sexpr,
-                         &mut self.qexpr,
-                         &mut self.rlisp]);
-    }
+struct Reader<'a> {
+    filename: &'a str,
+    input:    &'a str,
+    position: usize,
+    line:     u32,
+    column:   u32,
 }
 
-fn parse_ast(ast: &mpc::Ast) -> Option<Cell> {
+impl<'a> Reader<'a> {
+    fn new(filename: &'a str, input: &'a str, line: u32, column: u32) -> Reader<'a> {
+        Reader {
+            filename: filename,
+            input:    input,
+            position: 0,
+            line:     line,
+            column:   column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
 
-    let tag = ast.get_tag();
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
 
-    if tag.find("float").is_some() {
-        return match ast.get_contents().trim().parse() {
-            Ok(f)  => Some(Cell::Float(f)),
-            Err(_) => Some(Cell::Float(0.0)),
-        };
+        if let Some(c) = c {
+            self.position += c.len_utf8();
+
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        c
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            file:   self.filename.to_string(),
+            line:   self.line,
+            column: self.column,
+        }
+    }
+
+    fn error(&self, location: Location, message: &str) -> RlispError {
+        RlispError::new(ErrorKind::Parse, message.to_string(), Some(location))
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(';')                    => while let Some(c) = self.bump() { if c == '\n' { break; } },
+                Some(c) if c.is_whitespace() => { self.bump(); },
+                _                            => return,
+            }
+        }
     }
 
-    if tag.find("integer").is_some() {
-        return match ast.get_contents().trim().parse() {
-            Ok(i)  => Some(Cell::Integer(i)),
-            Err(_) => Some(Cell::Integer(0)),
+    // Skips the rest of a form that failed to read. Lists end at their closing
+    // delimiter and atoms at whitespace, so the forms after it can still be
+    // read. False when the input ends before the form does.
+    fn skip_form(&mut self) -> bool {
+        self.skip_whitespace();
+
+        let mut depth = 0;
+
+        while let Some(c) = self.bump() {
+            match c {
+                '(' | '{' => depth += 1,
+                ')' | '}' => {
+                    depth -= 1;
+
+                    if depth <= 0 {
+                        return true;
+                    }
+                },
+                '"'       => loop {
+                    match self.bump() {
+                        Some('\\') => { self.bump(); },
+                        Some('"')  => break,
+                        Some(_)    => (),
+                        None       => return false,
+                    }
+                },
+                '\''      => {
+                    if self.bump() == Some('\\') {
+                        self.bump();
+                    }
+                    self.bump();
+                },
+                ';'       => while let Some(c) = self.bump() { if c == '\n' { break; } },
+                c         => if depth == 0 && c.is_whitespace() {
+                    return true;
+                },
+            }
+        }
+
+        false
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.peek().is_none()
+    }
+
+    fn read(&mut self) -> Result<Option<Cell>, ReadError> {
+        self.skip_whitespace();
+
+        let location = self.location();
+
+        match self.peek() {
+            None      => Ok(None),
+            Some('(') => {
+                self.bump();
+                let v = try!(self.read_list(')', location.clone()));
+                Ok(Some(Cell::Sexpr(v, Some(Rc::new(location)))))
+            },
+            Some('{') => {
+                self.bump();
+                let v = try!(self.read_list('}', location));
+                Ok(Some(Cell::Qexpr(v)))
+            },
+            Some(c @ ')') |
+            Some(c @ '}') => Err(ReadError::Invalid(self.error(location, &format!("unexpected '{}'", c)))),
+            Some('"')     => self.read_string(location).map(Some),
+            Some('\'')    => self.read_char(location).map(Some),
+            Some('#')     => self.read_hash(location).map(Some),
+            Some(':') if self.at_type() => self.read_type(location).map(Some),
+            Some(c)       => self.read_atom(c, location).map(Some),
+        }
+    }
+
+    fn read_list(&mut self, close: char, open: Location) -> Result<Vec<Cell>, ReadError> {
+        let mut v = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                None                  => {
+                    let delimiter = if close == ')' { '(' } else { '{' };
+                    return Err(ReadError::Incomplete(self.error(open, &format!("unclosed '{}'", delimiter))));
+                },
+                Some(c) if c == close => { self.bump(); return Ok(v); },
+                Some(_)               => match try!(self.read()) {
+                    Some(c) => v.push(c),
+                    None    => (),
+                },
+            }
+        }
+    }
+
+    fn read_escape(&mut self, location: &Location) -> Result<char, ReadError> {
+        let escape_location = self.location();
+
+        match self.bump() {
+            Some('n')  => Ok('\n'),
+            Some('t')  => Ok('\t'),
+            Some('\\') => Ok('\\'),
+            Some('"')  => Ok('"'),
+            Some('\'') => Ok('\''),
+            Some('u')  => {
+                if self.bump() != Some('{') {
+                    return Err(ReadError::Invalid(self.error(escape_location, "expected '{' after \\u")));
+                }
+
+                let mut digits = String::new();
+
+                loop {
+                    match self.bump() {
+                        Some('}') => break,
+                        Some(c)   => digits.push(c),
+                        None      => return Err(ReadError::Incomplete(self.error(location.clone(), "unterminated unicode escape"))),
+                    }
+                }
+
+                match u32::from_str_radix(&digits[..], 16).ok().and_then(char::from_u32) {
+                    Some(c) if digits.len() <= 6 => Ok(c),
+                    _                            => Err(ReadError::Invalid(self.error(escape_location, "invalid unicode escape"))),
+                }
+            },
+            Some(c)    => Err(ReadError::Invalid(self.error(escape_location, &format!("unknown escape '\\{}'", c)))),
+            None       => Err(ReadError::Incomplete(self.error(location.clone(), "unterminated escape"))),
+        }
+    }
+
+    fn read_string(&mut self, location: Location) -> Result<Cell, ReadError> {
+        let mut s = String::new();
+        self.bump();
+
+        loop {
+            match self.bump() {
+                Some('"')  => return Ok(Cell::Str(s)),
+                Some('\\') => s.push(try!(self.read_escape(&location))),
+                Some(c)    => s.push(c),
+                None       => return Err(ReadError::Incomplete(self.error(location, "unterminated string"))),
+            }
+        }
+    }
+
+    fn read_char(&mut self, location: Location) -> Result<Cell, ReadError> {
+        self.bump();
+
+        let c = match self.bump() {
+            Some('\\') => try!(self.read_escape(&location)),
+            Some('\'') => return Err(ReadError::Invalid(self.error(location, "empty char"))),
+            Some(c)    => c,
+            None       => return Err(ReadError::Incomplete(self.error(location, "unterminated char"))),
         };
+
+        match self.bump() {
+            Some('\'') => Ok(Cell::Char(c)),
+            None       => Err(ReadError::Incomplete(self.error(location, "unterminated char"))),
+            Some(_)    => Err(ReadError::Invalid(self.error(location, "invalid char"))),
+        }
     }
 
-    if tag.find("string").is_some() {
-        let s = ast.get_contents();
-        return Some(Cell::Str(s[1 .. s.len() - 1].to_string()));
+    fn read_hash(&mut self, location: Location) -> Result<Cell, ReadError> {
+        self.bump();
+
+        match self.bump() {
+            Some('t') => Ok(Cell::Bool(true)),
+            Some('f') => Ok(Cell::Bool(false)),
+            Some('{') => self.read_map(location),
+            None      => Err(ReadError::Incomplete(self.error(location, "unterminated bool"))),
+            Some(_)   => Err(ReadError::Invalid(self.error(location, "expected #t, #f or #{"))),
+        }
     }
 
-    if tag.find("char").is_some() {
-        let s = ast.get_contents();
-        return Some(Cell::Char(s.chars().next().expect("Internal parse error")));
+    fn read_map(&mut self, location: Location) -> Result<Cell, ReadError> {
+        let v = try!(self.read_list('}', location.clone()));
+
+        if v.len() % 2 != 0 {
+            return Err(ReadError::Invalid(self.error(location, "map needs a value for every key")));
+        }
+
+        let mut map = BTreeMap::new();
+
+        for pair in v.chunks(2) {
+            match Key::from_cell(&pair[0]) {
+                Some(key) => { map.insert(key, pair[1].clone()); },
+                None      => {
+                    let message = format!("{} can't be used as a map key", pair[0].get_type());
+                    return Err(ReadError::Invalid(self.error(location, &message)));
+                },
+            }
+        }
+
+        Ok(Cell::Map(Box::new(map)))
     }
 
-    if tag.find("bool").is_some() {
-        let s = ast.get_contents();
+    // A ':' only starts a type when a list or a known type name follows,
+    // otherwise it's the start of a symbol like :key or :Map-key
+    fn at_type(&self) -> bool {
+        let rest = &self.input[self.position + 1..];
+
+        match rest.chars().next() {
+            Some('{') | Some('(') | Some('[') => true,
+            _                                 => {
+                let end = rest.find(|c| !is_type_name(c)).unwrap_or(rest.len());
+                let ends_name = match rest[end..].chars().next() {
+                    Some('|') | Some('.') => true,
+                    Some(c)               => !is_symbol_start(c) && !is_digit(c),
+                    None                  => true,
+                };
 
-        if s == "#t" {
-            return Some(Cell::Bool(true));
-        } else {
-            return Some(Cell::Bool(false));
+                ends_name && Type::from_name(&rest[..end]).is_some()
+            },
         }
     }
 
-    if tag.find("symbol").is_some() {
-        let s = ast.get_contents();
-        return Some(Cell::Symbol(s.to_string()));
+    fn read_type(&mut self, location: Location) -> Result<Cell, ReadError> {
+        self.bump();
+        let t = try!(self.read_type_expr(&location));
+        Ok(Cell::Type(Box::new(t)))
     }
 
-    if tag.find("qexpr").is_some() {    
-        let mut res: Vec<Cell> = Vec::new();
+    fn read_type_expr(&mut self, location: &Location) -> Result<Type, ReadError> {
+        let t = try!(self.read_type_alternatives(location));
 
-        for c in ast.child_iter().skip(1).take((ast.get_no_children() - 2) as usize) {
-            if let Some(s) = parse_ast(&c) {
-                res.push(s);
+        if self.input[self.position..].starts_with("...") {
+            for _ in 0..3 {
+                self.bump();
             }
+            return Ok(RElipsisT(Box::new(t)));
         }
 
-       return Some(Cell::Qexpr(res));
+        Ok(t)
     }
 
-    if tag.find("sexpr").is_some() {
-        let mut res: Vec<Cell> = Vec::new();
+    fn read_type_alternatives(&mut self, location: &Location) -> Result<Type, ReadError> {
+        let t = try!(self.read_type_base(location));
+
+        if self.peek() == Some('|') {
+            self.bump();
+            let alternative = try!(self.read_type_alternatives(location));
+            return Ok(ROrT(Box::new(t), Box::new(alternative)));
+        }
+
+        Ok(t)
+    }
+
+    fn read_type_base(&mut self, location: &Location) -> Result<Type, ReadError> {
+        let type_location = self.location();
+
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                self.read_type_list('}', location).map(RQexprT)
+            },
+            Some('(') => {
+                self.bump();
+                self.read_type_list(')', location).map(RSexprT)
+            },
+            Some('[') => {
+                self.bump();
+                let t = try!(self.read_type_expr(location));
 
-        for c in ast.child_iter().skip(1).take((ast.get_no_children() - 2) as usize) {
-            if let Some(s) = parse_ast(&c) {
-                res.push(s);
+                match self.bump() {
+                    Some(']') => Ok(ROptionalT(Box::new(t))),
+                    None      => Err(ReadError::Incomplete(self.error(location.clone(), "unterminated type"))),
+                    Some(_)   => Err(ReadError::Invalid(self.error(type_location, "expected ']'"))),
+                }
+            },
+            Some(c) if is_type_name(c) => {
+                let start = self.position;
+
+                while let Some(c) = self.peek() {
+                    if is_type_name(c) {
+                        self.bump();
+                    } else {
+                        break;
+                    }
+                }
+
+                let name = &self.input[start..self.position];
+
+                match Type::from_name(name) {
+                    Some(t) => Ok(t),
+                    None    => Err(ReadError::Invalid(self.error(type_location, &format!("unknown type {}", name)))),
+                }
+            },
+            None      => Err(ReadError::Incomplete(self.error(location.clone(), "unterminated type"))),
+            Some(_)   => Err(ReadError::Invalid(self.error(type_location, "expected a type"))),
+        }
+    }
+
+    fn read_type_list(&mut self, close: char, location: &Location) -> Result<Vec<Type>, ReadError> {
+        let list_location = self.location();
+        let mut v = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                None                  => return Err(ReadError::Incomplete(self.error(location.clone(), "unterminated type"))),
+                Some(c) if c == close => { self.bump(); break; },
+                Some(_)               => v.push(try!(self.read_type_expr(location))),
             }
         }
 
-       return Some(Cell::Sexpr(res));
+        match types::check_arity(&v[..]) {
+            Ok(_)  => Ok(v),
+            Err(e) => Err(ReadError::Invalid(self.error(list_location, &e))),
+        }
     }
 
-    if tag.find("comment").is_some() {
-       return None;
+    fn read_atom(&mut self, c: char, location: Location) -> Result<Cell, ReadError> {
+        let start = self.position;
+
+        if let Some((length, is_float)) = number_length(&self.input[start..]) {
+            for _ in 0..length {
+                self.bump();
+            }
+
+            let s = self.input[start..self.position].trim_start_matches('+');
+
+            return if is_float {
+                match s.parse() {
+                    Ok(f)  => Ok(Cell::Float(f)),
+                    Err(_) => Err(ReadError::Invalid(self.error(location, "invalid float"))),
+                }
+            } else {
+                match s.parse() {
+                    Ok(i)  => Ok(Cell::Integer(i)),
+                    Err(_) => Err(ReadError::Invalid(self.error(location, "integer out of range"))),
+                }
+            };
+        }
+
+        if !is_symbol_start(c) {
+            return Err(ReadError::Invalid(self.error(location, &format!("unexpected '{}'", c))));
+        }
+
+        while let Some(c) = self.peek() {
+            if is_symbol_start(c) || is_digit(c) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        Ok(Cell::Symbol(self.input[start..self.position].to_string()))
     }
+}
+
+pub fn escape(s: &str, quote: char) -> String {
+    let mut escaped = String::new();
 
-    if tag == ">" {
-        return parse_ast(&ast.get_child(1).expect("Internal grammer error"));
+    for c in s.chars() {
+        match c {
+            '\n'                => escaped.push_str("\\n"),
+            '\t'                => escaped.push_str("\\t"),
+            '\\'                => escaped.push_str("\\\\"),
+            c if c == quote     => { escaped.push('\\'); escaped.push(c); },
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c                   => escaped.push(c),
+        }
     }
 
-    None
-}
\ No newline at end of file
+    escaped
+}
+
+fn is_type_name(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' => true,
+        _                     => false,
+    }
+}
+
+fn is_digit(c: char) -> bool {
+    match c {
+        '0'...'9' => true,
+        _         => false,
+    }
+}
+
+fn is_symbol_start(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' |
+        '!' | '$' | '%' | '&' | '*' | '+' | '-' | '.' | '/' | ':' |
+        '<' | '=' | '>' | '?' | '@' | '^' | '_' | '~' | '\\' => true,
+        _                                                   => false,
+    }
+}
+
+fn digits_length(b: &[u8]) -> usize {
+    b.iter().take_while(|&&c| c >= b'0' && c <= b'9').count()
+}
+
+// float   : [-+]?[0-9]*\.[0-9]+([eE][-+]?[0-9]+)? or [-+]?[0-9]+[eE][-+]?[0-9]+
+// integer : [-+]?[0-9]+
+fn number_length(s: &str) -> Option<(usize, bool)> {
+    let b = s.as_bytes();
+    let mut i = 0;
+    let mut is_float = false;
+
+    if i < b.len() && (b[i] == b'+' || b[i] == b'-') {
+        i += 1;
+    }
+
+    let integer_digits = digits_length(&b[i..]);
+    i += integer_digits;
+
+    if i < b.len() && b[i] == b'.' {
+        let fraction_digits = digits_length(&b[i + 1..]);
+
+        if fraction_digits > 0 {
+            i += 1 + fraction_digits;
+            is_float = true;
+        }
+    }
+
+    if integer_digits == 0 && !is_float {
+        return None;
+    }
+
+    if i < b.len() && (b[i] == b'e' || b[i] == b'E') {
+        let mut j = i + 1;
+
+        if j < b.len() && (b[j] == b'+' || b[j] == b'-') {
+            j += 1;
+        }
+
+        let exponent_digits = digits_length(&b[j..]);
+
+        if exponent_digits > 0 {
+            i = j + exponent_digits;
+            is_float = true;
+        }
+    }
+
+    Some((i, is_float))
+}
+
+pub struct Parser {
+    buffer: String,
+    line:   u32,
+    column: u32,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            buffer: String::new(),
+            line:   1,
+            column: 1,
+        }
+    }
+
+    pub fn parse(&self, filename: &str, input: &str) -> Result<Cell, RlispError> {
+        let exprs = try!(self.parse_all(filename, input));
+        Ok(exprs.into_iter().next().unwrap_or(Cell::Nil))
+    }
+
+    pub fn parse_all(&self, filename: &str, input: &str) -> Result<Vec<Cell>, RlispError> {
+        let mut reader = Reader::new(filename, input, 1, 1);
+        let mut exprs = Vec::new();
+
+        loop {
+            match reader.read() {
+                Ok(Some(c)) => exprs.push(c),
+                Ok(None)    => break,
+                Err(e)      => return Err(e.into_error()),
+            }
+        }
+
+        Ok(exprs)
+    }
+
+    pub fn feed(&mut self, input: &str) {
+        self.buffer.push_str(input);
+    }
+
+    // The next form in the fed input, None until a whole form has been fed
+    pub fn next(&mut self, filename: &str) -> Option<Result<Cell, RlispError>> {
+        self.read(filename, false)
+    }
+
+    // Like next, but no more input will be fed. A trailing atom is read and
+    // an unfinished form is an error.
+    pub fn finish(&mut self, filename: &str) -> Option<Result<Cell, RlispError>> {
+        self.read(filename, true)
+    }
+
+    fn read(&mut self, filename: &str, complete: bool) -> Option<Result<Cell, RlispError>> {
+        let (result, position, line, column) = {
+            let mut reader = Reader::new(filename, &self.buffer[..], self.line, self.column);
+
+            let result = match reader.read() {
+                Ok(Some(c)) => {
+                    // An atom at the very end of the buffer might continue in the next chunk
+                    if !complete && reader.peek().is_none() && !self.buffer.ends_with(|l| l == ')' || l == '}' || l == '"' || l == '\'') {
+                        None
+                    } else {
+                        Some(Ok(c))
+                    }
+                },
+                Ok(None)                      => None,
+                Err(ReadError::Incomplete(e)) => {
+                    if complete {
+                        while reader.bump().is_some() {}
+                        Some(Err(e))
+                    } else {
+                        None
+                    }
+                },
+                // The error is only reported once the rest of the form can be skipped
+                Err(ReadError::Invalid(e))    => {
+                    reader = Reader::new(filename, &self.buffer[..], self.line, self.column);
+
+                    if reader.skip_form() || complete {
+                        Some(Err(e))
+                    } else {
+                        None
+                    }
+                },
+            };
+
+            match result {
+                Some(_) => (result, reader.position, reader.line, reader.column),
+                None    => return None,
+            }
+        };
+
+        self.buffer = self.buffer[position..].to_string();
+        self.line = line;
+        self.column = column;
+
+        result
+    }
+
+    pub fn is_pending(&self) -> bool {
+        let mut reader = Reader::new("", &self.buffer[..], self.line, self.column);
+        !reader.at_end()
+    }
+}
+
+#[test]
+fn test_parse() {
+    let parser = Parser::new();
+
+    assert_eq!(parser.parse("test", "(+ 1 -2.5 \"a b\" 'c' #t sym ; comment\n {x ...})"),
+               Ok(Cell::Sexpr(vec![Cell::Symbol("+".to_string()),
+                                   Cell::Integer(1),
+                                   Cell::Float(-2.5),
+                                   Cell::Str("a b".to_string()),
+                                   Cell::Char('c'),
+                                   Cell::Bool(true),
+                                   Cell::Symbol("sym".to_string()),
+                                   Cell::Qexpr(vec![Cell::Symbol("x".to_string()),
+                                                    Cell::Symbol("...".to_string())])], None)));
+
+    assert_eq!(parser.parse("test", "-"), Ok(Cell::Symbol("-".to_string())));
+    assert_eq!(parser.parse("test", "+5"), Ok(Cell::Integer(5)));
+    assert_eq!(parser.parse("test", "1e3"), Ok(Cell::Float(1000.0)));
+    assert_eq!(parser.parse("test", "; only a comment"), Ok(Cell::Nil));
+    assert_eq!(parser.parse("test", ""), Ok(Cell::Nil));
+}
+
+#[test]
+fn test_parse_escapes() {
+    let parser = Parser::new();
+
+    assert_eq!(parser.parse("test", r#""a\n\tb \\ \"c\" \u{e5}""#), Ok(Cell::Str("a\n\tb \\ \"c\" \u{e5}".to_string())));
+    assert_eq!(parser.parse("test", r"'\n'"), Ok(Cell::Char('\n')));
+    assert_eq!(parser.parse("test", r"'\''"), Ok(Cell::Char('\'')));
+    assert_eq!(parser.parse("test", r"'\u{1F600}'"), Ok(Cell::Char('\u{1F600}')));
+
+    let e = parser.parse("test", r#""a\qb""#).unwrap_err();
+    assert_eq!(e.message, "unknown escape '\\q'");
+
+    let e = parser.parse("test", r"'\u{110000}'").unwrap_err();
+    assert_eq!(e.message, "invalid unicode escape");
+}
+
+#[test]
+fn test_parse_types() {
+    let parser = Parser::new();
+
+    assert_eq!(parser.parse("test", ":Integer"), Ok(Cell::Type(Box::new(IntegerT))));
+    assert_eq!(parser.parse("test", ":{Integer Float...}"),
+               Ok(Cell::Type(Box::new(RQexprT(vec![IntegerT, RElipsisT(Box::new(FloatT))])))));
+    assert_eq!(parser.parse("test", ":(Symbol [Integer|Float])"),
+               Ok(Cell::Type(Box::new(RSexprT(vec![SymbolT, ROptionalT(Box::new(ROrT(Box::new(IntegerT), Box::new(FloatT))))])))));
+    assert_eq!(parser.parse("test", ":Integer|Float..."),
+               Ok(Cell::Type(Box::new(RElipsisT(Box::new(ROrT(Box::new(IntegerT), Box::new(FloatT))))))));
+
+    assert_eq!(parser.parse("test", ":Integr"), Ok(Cell::Symbol(":Integr".to_string())));
+    assert_eq!(parser.parse("test", ":key"), Ok(Cell::Symbol(":key".to_string())));
+    assert_eq!(parser.parse("test", ":Map-key"), Ok(Cell::Symbol(":Map-key".to_string())));
+
+    let e = parser.parse("test", ":[Integr]").unwrap_err();
+    assert_eq!(e.message, "unknown type Integr");
+
+    let e = parser.parse("test", ":{Integer... Float}").unwrap_err();
+    assert_eq!(e.message, "Float after an elipsis");
+}
+
+#[test]
+fn test_parse_map() {
+    let parser = Parser::new();
+
+    let mut map = BTreeMap::new();
+    map.insert(Key::Str("a".to_string()), Cell::Integer(1));
+    map.insert(Key::Symbol("b".to_string()), Cell::Qexpr(vec![Cell::Integer(2)]));
+
+    assert_eq!(parser.parse("test", "#{\"a\" 1 b {2}}"), Ok(Cell::Map(Box::new(map))));
+    assert_eq!(parser.parse("test", "#{}"), Ok(Cell::Map(Box::new(BTreeMap::new()))));
+
+    let e = parser.parse("test", "#{1 2 3}").unwrap_err();
+    assert_eq!(e.message, "map needs a value for every key");
+
+    let e = parser.parse("test", "#{1.5 2}").unwrap_err();
+    assert_eq!(e.message, "Float can't be used as a map key");
+}
+
+#[test]
+fn test_parse_all() {
+    let parser = Parser::new();
+
+    assert_eq!(parser.parse_all("test", "(a 1) b ; c\n{2}"),
+               Ok(vec![Cell::Sexpr(vec![Cell::Symbol("a".to_string()), Cell::Integer(1)], None),
+                       Cell::Symbol("b".to_string()),
+                       Cell::Qexpr(vec![Cell::Integer(2)])]));
+    assert_eq!(parser.parse_all("test", "  ; nothing"), Ok(Vec::new()));
+    assert!(parser.parse_all("test", "a (b").is_err());
+}
+
+#[test]
+fn test_parse_error() {
+    let parser = Parser::new();
+
+    let e = parser.parse("test", "(+ 1\n  (- 2)").unwrap_err();
+    assert_eq!(e.message, "unclosed '('");
+
+    let e = parser.parse("test", "(+ 1\n  2))").unwrap_err();
+    assert_eq!(e.message, "unexpected ')'");
+    assert_eq!(e.location, Some(Location { file: "test".to_string(), line: 2, column: 5 }));
+
+    let e = parser.parse("test", "(#x)").unwrap_err();
+    assert_eq!(e.message, "expected #t, #f or #{");
+    assert_eq!(e.location, Some(Location { file: "test".to_string(), line: 1, column: 2 }));
+}
+
+#[test]
+fn test_parse_incremental() {
+    let mut parser = Parser::new();
+
+    parser.feed("(+ 1");
+    assert!(parser.next("test").is_none());
+    assert!(parser.is_pending());
+
+    parser.feed("\n  2) {a}");
+    assert_eq!(parser.next("test"),
+               Some(Ok(Cell::Sexpr(vec![Cell::Symbol("+".to_string()), Cell::Integer(1), Cell::Integer(2)], None))));
+    assert_eq!(parser.next("test"), Some(Ok(Cell::Qexpr(vec![Cell::Symbol("a".to_string())]))));
+    assert!(parser.next("test").is_none());
+    assert!(!parser.is_pending());
+
+    parser.feed("12");
+    assert!(parser.next("test").is_none());
+    parser.feed("3 )");
+    assert_eq!(parser.next("test"), Some(Ok(Cell::Integer(123))));
+
+    let e = parser.next("test").unwrap().unwrap_err();
+    assert_eq!(e.location.map(|l| (l.line, l.column)), Some((2, 13)));
+    assert!(!parser.is_pending());
+
+    parser.feed("(a #x\n b) #y 2\n{c}");
+    assert!(parser.next("test").unwrap().is_err());
+    assert!(parser.next("test").unwrap().is_err());
+    assert_eq!(parser.next("test"), Some(Ok(Cell::Integer(2))));
+    assert_eq!(parser.next("test"), Some(Ok(Cell::Qexpr(vec![Cell::Symbol("c".to_string())]))));
+
+    // The error waits for the rest of the form so the form is skipped as a whole
+    parser.feed("(a #x {b");
+    assert!(parser.next("test").is_none());
+    parser.feed(" c}) 3 ");
+    assert!(parser.next("test").unwrap().is_err());
+    assert_eq!(parser.next("test"), Some(Ok(Cell::Integer(3))));
+
+    parser.feed("42");
+    assert!(parser.next("test").is_none());
+    assert_eq!(parser.finish("test"), Some(Ok(Cell::Integer(42))));
+    assert!(parser.finish("test").is_none());
+
+    parser.feed("(+ 1");
+    assert_eq!(parser.finish("test").unwrap().unwrap_err().message, "unclosed '('");
+    assert!(!parser.is_pending());
+}
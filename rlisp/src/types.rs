@@ -25,10 +25,18 @@ pub enum Type {
     ErrorT,
     BuiltinT,
     LambdaT,
+    MacroT,
+    TypeT,
+    MapT,
+    FileT,
+    ContinuationT,
     AnyT,
     ElipsisT(&'static Type),
     OptionalT(&'static Type),
     OrT(&'static Type, &'static Type),
+    RElipsisT(Box<Type>),
+    ROptionalT(Box<Type>),
+    ROrT(Box<Type>, Box<Type>),
 }
 
 pub struct Arity {
@@ -97,13 +105,131 @@ impl Type {
                 temp.push_str("}");
                 Owned(temp)
             },
-            ErrorT               => Borrowed("Error"),
-            BuiltinT             => Borrowed("Builtin"),
-            LambdaT              => Borrowed("Lambda"),
-            AnyT                 => Borrowed("Any"),
-            ElipsisT(ref inner)  => Owned(format!("{}...", inner)),
-            OptionalT(ref inner) => Owned(format!("[{}]", inner)),
-            OrT(ref i1, ref i2)  => Owned(format!("{}|{}", i1, i2)),
+            ErrorT                => Borrowed("Error"),
+            BuiltinT              => Borrowed("Builtin"),
+            LambdaT               => Borrowed("Lambda"),
+            MacroT                => Borrowed("Macro"),
+            TypeT                 => Borrowed("Type"),
+            MapT                  => Borrowed("Map"),
+            FileT                 => Borrowed("File"),
+            ContinuationT         => Borrowed("Continuation"),
+            AnyT                  => Borrowed("Any"),
+            ElipsisT(ref inner)   => Owned(format!("{}...", inner)),
+            OptionalT(ref inner)  => Owned(format!("[{}]", inner)),
+            OrT(ref i1, ref i2)   => Owned(format!("{}|{}", i1, i2)),
+            RElipsisT(ref inner)  => Owned(format!("{}...", inner)),
+            ROptionalT(ref inner) => Owned(format!("[{}]", inner)),
+            ROrT(ref i1, ref i2)  => Owned(format!("{}|{}", i1, i2)),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "Nil"          => Some(NilT),
+            "Symbol"       => Some(SymbolT),
+            "Integer"      => Some(IntegerT),
+            "Float"        => Some(FloatT),
+            "Char"         => Some(CharT),
+            "Bool"         => Some(BoolT),
+            "String"       => Some(StringT),
+            "Error"        => Some(ErrorT),
+            "Builtin"      => Some(BuiltinT),
+            "Lambda"       => Some(LambdaT),
+            "Macro"        => Some(MacroT),
+            "Type"         => Some(TypeT),
+            "Map"          => Some(MapT),
+            "File"         => Some(FileT),
+            "Continuation" => Some(ContinuationT),
+            "Any"          => Some(AnyT),
+            _              => None,
+        }
+    }
+
+    pub fn is_elipsis(&self) -> bool {
+        match *self {
+            ElipsisT(_) | RElipsisT(_) => true,
+            _                          => false,
+        }
+    }
+
+    pub fn is_optional(&self) -> bool {
+        match *self {
+            OptionalT(_) | ROptionalT(_) => true,
+            _                            => false,
+        }
+    }
+
+    fn sexpr_types(&self) -> Option<&[Type]> {
+        match *self {
+            SexprT(v)      => Some(v),
+            RSexprT(ref v) => Some(&v[..]),
+            _              => None,
+        }
+    }
+
+    fn qexpr_types(&self) -> Option<&[Type]> {
+        match *self {
+            QexprT(v)      => Some(v),
+            RQexprT(ref v) => Some(&v[..]),
+            _              => None,
+        }
+    }
+
+    fn inner(&self) -> Option<&Type> {
+        match *self {
+            ElipsisT(inner) | OptionalT(inner) => Some(inner),
+            RElipsisT(ref inner)  => Some(&**inner),
+            ROptionalT(ref inner) => Some(&**inner),
+            _                                  => None,
+        }
+    }
+
+    fn alternatives(&self) -> Option<(&Type, &Type)> {
+        match *self {
+            OrT(i1, i2)          => Some((i1, i2)),
+            ROrT(ref i1, ref i2)  => Some((&**i1, &**i2)),
+            _                    => None,
+        }
+    }
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Type) -> bool {
+        if let (Some(a), Some(b)) = (self.sexpr_types(), other.sexpr_types()) {
+            return a == b;
+        }
+
+        if let (Some(a), Some(b)) = (self.qexpr_types(), other.qexpr_types()) {
+            return a == b;
+        }
+
+        if let (Some((a1, a2)), Some((b1, b2))) = (self.alternatives(), other.alternatives()) {
+            return a1 == b1 && a2 == b2;
+        }
+
+        if let (Some(a), Some(b)) = (self.inner(), other.inner()) {
+            return self.is_elipsis() == other.is_elipsis() && a == b;
+        }
+
+        match (self, other) {
+            (&NilT, &NilT)         => true,
+            (&SymbolT, &SymbolT)   => true,
+            (&IntegerT, &IntegerT) => true,
+            (&FloatT, &FloatT)     => true,
+            (&CharT, &CharT)       => true,
+            (&BoolT, &BoolT)       => true,
+            (&StringT, &StringT)   => true,
+            (&ErrorT, &ErrorT)     => true,
+            (&BuiltinT, &BuiltinT) => true,
+            (&LambdaT, &LambdaT)   => true,
+            (&MacroT, &MacroT)     => true,
+            (&TypeT, &TypeT)       => true,
+            (&MapT, &MapT)         => true,
+            (&FileT, &FileT)       => true,
+            (&ContinuationT,
+             &ContinuationT)       => true,
+            (&AnyT, &AnyT)         => true,
+            _                      => false,
         }
     }
 }
@@ -121,6 +247,13 @@ impl fmt::Debug for Type {
 }
 
 pub fn get_arity(argument_types: &[Type]) -> Arity {
+    match check_arity(argument_types) {
+        Ok(arity) => arity,
+        Err(_)    => panic!("Internal type error"),
+    }
+}
+
+pub fn check_arity(argument_types: &[Type]) -> Result<Arity, String> {
     let mut arity = Arity {
         requierd: 0,
         optional: 0,
@@ -131,69 +264,68 @@ pub fn get_arity(argument_types: &[Type]) -> Arity {
     let mut found_elipsis = false;
 
     for t in argument_types.iter() {
-        match t {
-            &ElipsisT(_) => {
-                arity.elipsis = true;
-                found_elipsis = true;
-            },
-            &OptionalT(_) => {
-                if found_elipsis {
-                    panic!("Internal type error");
-                }
-                found_optional = true;
-                arity.optional += 1;
-            },
-            _ => {
-                if found_optional {
-                    panic!("Internal type error");
-                }
-                if found_elipsis {
-                    panic!("Internal type error");
-                }
-                arity.requierd += 1;
+        if found_elipsis {
+            return Err(format!("{} after an elipsis", t));
+        }
+
+        if t.is_elipsis() {
+            arity.elipsis = true;
+            found_elipsis = true;
+        } else if t.is_optional() {
+            found_optional = true;
+            arity.optional += 1;
+        } else {
+            if found_optional {
+                return Err(format!("{} after an optional", t));
             }
+            arity.requierd += 1;
         }
     }
 
-    arity
+    Ok(arity)
+}
+
+pub fn default_argument_type(argument: &str) -> Type {
+    if argument == "..." {
+        RElipsisT(Box::new(AnyT))
+    } else {
+        AnyT
+    }
+}
+
+pub fn matches(argument_types: &[Type], args: &[cell::Cell]) -> bool {
+    validate_inner(argument_types, args).is_none()
 }
 
 fn validate_inner(argument_types: &[Type], args: &[cell::Cell]) -> Option<String> {
     let mut arg_type_iter = argument_types.iter();
-    let mut arg_type = arg_type_iter.next().expect("Internal error");
+    let mut arg_type = match arg_type_iter.next() {
+        Some(t) => t,
+        None    => return if args.is_empty() {
+            None
+        } else {
+            Some(format!("requiers 0 arguments, got {}", args.len()))
+        },
+    };
 
     for (i, arg) in args.iter().enumerate() {
 
-        match (arg, arg_type) {
-            (&cell::Cell::Sexpr(ref v), &SexprT(ref vt)) => {
-                if let Some(e) = validate_inner(&vt[..], &v[..]) {
-                    return Some(format!("argument list at {}: {}", i+1, e));
-                }
-            },
-            (&cell::Cell::Sexpr(ref v), &RSexprT(ref vt)) => {
-                if let Some(e) = validate_inner(&vt[..], &v[..]) {
-                    return Some(format!("argument list at {}: {}", i+1, e));
-                }
-            },
-            (&cell::Cell::Qexpr(ref v), &QexprT(ref vt)) => {
-                if let Some(e) = validate_inner(&vt[..], &v[..]) {
-                    return Some(format!("argument list at {}: {}", i+1, e));
-                }
-            },
-            (&cell::Cell::Qexpr(ref v), &RQexprT(ref vt)) => {
-                if let Some(e) = validate_inner(&vt[..], &v[..]) {
-                    return Some(format!("argument list at {}: {}", i+1, e));
-                }
-            },
-            (_, _) => (),
-        }
+        let list = match (arg, arg_type.sexpr_types(), arg_type.qexpr_types()) {
+            (&cell::Cell::Sexpr(ref v, _), Some(vt), _) => Some(validate_inner(vt, &v[..])),
+            (&cell::Cell::Qexpr(ref v), _, Some(vt))    => Some(validate_inner(vt, &v[..])),
+            (_, _, _)                                   => None,
+        };
 
-        if !arg.is_type(arg_type) {
-            return Some(format!("argument {} is of type {} expected {}",
-                                i+1, arg.get_type(), arg_type));
+        match list {
+            Some(Some(e)) => return Some(format!("argument list at {}: {}", i+1, e)),
+            Some(None)    => (),
+            None          => if !arg.is_type(arg_type) {
+                return Some(format!("argument {} is of type {} expected {}",
+                                    i+1, arg.get_type(), arg_type));
+            },
         }
 
-        if let &ElipsisT(_) = arg_type {} else {
+        if arg_type.is_elipsis() {} else {
             arg_type = match arg_type_iter.next() {
                 Some(t) => t,
                 None    => break,
@@ -226,8 +358,12 @@ fn validate_inner(argument_types: &[Type], args: &[cell::Cell]) -> Option<String
 }
 
 pub fn validate(f: &cell::BuiltinFunctionSpec, args: &[cell::Cell]) -> Option<String> {
-    match validate_inner(&f.argument_types[..], args) {
-        Some(e) => Some(format!("{}, {}", f.name, e)),
+    validate_arguments(f.name, &f.argument_types[..], args)
+}
+
+pub fn validate_arguments(name: &str, argument_types: &[Type], args: &[cell::Cell]) -> Option<String> {
+    match validate_inner(argument_types, args) {
+        Some(e) => Some(format!("{}, {}", name, e)),
         None    => None,
     }
 }
@@ -264,5 +400,5 @@ fn test_validate() {
     assert_eq!(validate(stub_def, &[]), Some("def, requiers 2 arguments, got 0".to_string()));
     assert_eq!(validate(stub_def, &[Qexpr(vec![Symbol("hej".to_string())]), Integer(1)]), None);
     assert_eq!(validate(stub_def, &[Qexpr(vec![Integer(1)]), Integer(1)]), Some("def, argument list at 1: argument 1 is of type Integer expected Symbol".to_string()));
-    assert_eq!(validate(stub_def, &[Qexpr(vec![Symbol("hej".to_string()), Integer(1)]), Integer(1)]), Some("def, argument list at 1: argument 2 is of type Integer expected Symbol...".to_string()));
+    assert_eq!(validate(stub_def, &[Qexpr(vec![Symbol("hej".to_string()), Integer(1)]), Integer(1)]), Some("def, argument list at 1: argument 2 is of type Integer expected Symbol|Type...".to_string()));
 }
\ No newline at end of file
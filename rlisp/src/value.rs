@@ -0,0 +1,130 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use cell::Cell;
+use parser;
+
+use self::Value::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Nil,
+    Symbol(String),
+    Integer(i64),
+    Float(f64),
+    Char(char),
+    Bool(bool),
+    Str(String),
+    Sexpr(Vec<Value>),
+    Qexpr(Vec<Value>),
+    Error(String),
+    Function(String),
+    Type(String),
+    Map(Vec<(Value, Value)>),
+    File(String),
+}
+
+impl<'a> From<&'a Cell> for Value {
+    fn from(c: &'a Cell) -> Value {
+        match c {
+            &Cell::Nil               => Nil,
+            &Cell::Symbol(ref s)     => Symbol(s.clone()),
+            &Cell::Integer(i)        => Integer(i),
+            &Cell::Float(f)          => Float(f),
+            &Cell::Char(c)           => Char(c),
+            &Cell::Bool(b)           => Bool(b),
+            &Cell::Str(ref s)        => Str(s.clone()),
+            &Cell::Sexpr(ref v, _)   => Sexpr(v.iter().map(Value::from).collect()),
+            &Cell::Qexpr(ref v)      => Qexpr(v.iter().map(Value::from).collect()),
+            &Cell::Error(ref e)      => Error(e.message.clone()),
+            &Cell::Builtin(_)        |
+            &Cell::CurriedBuiltin(_) |
+            &Cell::Native(_)         |
+            &Cell::Lambda(_)         |
+            &Cell::Macro(_)          |
+            &Cell::Continuation(_)   => Function(format!("{}", c)),
+            &Cell::Type(ref t)       => Type(format!("{}", t)),
+            &Cell::Map(ref m)        => Map(m.iter().map(|(k, v)| (Value::from(&k.to_cell()), Value::from(v))).collect()),
+            &Cell::File(_)           => File(format!("{}", c)),
+        }
+    }
+}
+
+impl Value {
+    fn to_string<'a>(&self) -> Cow<'a, str> {
+        use std::borrow::Cow::{Borrowed, Owned};
+
+        match self {
+            &Nil             => Borrowed("()"),
+            &Symbol(ref sym) => Owned(format!("{}", sym)),
+            &Integer(ref i)  => Owned(format!("{}", i)),
+            &Float(ref fl)   => Owned(format!("{:.2}", fl)),
+            &Char(ref c)     => Owned(format!("'{}'", parser::escape(&c.to_string(), '\''))),
+            &Bool(ref b)     => if *b { Borrowed("#t") } else { Borrowed("#f") },
+            &Str(ref s)      => Owned(format!("\"{}\"", parser::escape(s, '"'))),
+            &Sexpr(ref v)    => {
+                let mut temp: String = "(".to_string();
+                for i in 0..v.len() {
+                    if i == v.len() - 1 {
+                        temp.push_str(&format!("{}", v[i])[..]);
+                    } else {
+                        temp.push_str(&format!("{} ", v[i])[..]);
+                    }
+                }
+                temp.push_str(")");
+                Owned(temp)
+            },
+            &Qexpr(ref v) => {
+                let mut temp: String = "{".to_string();
+                for i in 0..v.len() {
+                    if i == v.len() - 1 {
+                        temp.push_str(&format!("{}", v[i])[..]);
+                    } else {
+                        temp.push_str(&format!("{} ", v[i])[..]);
+                    }
+                }
+                temp.push_str("}");
+                Owned(temp)
+            },
+            &Error(ref e)    => Owned(format!("Error: {}", e)),
+            &Function(ref f) => Owned(f.clone()),
+            &Type(ref t)     => Owned(format!(":{}", t)),
+            &Map(ref m)      => {
+                let mut temp: String = "#{".to_string();
+                for i in 0..m.len() {
+                    if i == m.len() - 1 {
+                        temp.push_str(&format!("{} {}", m[i].0, m[i].1)[..]);
+                    } else {
+                        temp.push_str(&format!("{} {} ", m[i].0, m[i].1)[..]);
+                    }
+                }
+                temp.push_str("}");
+                Owned(temp)
+            },
+            &File(ref f)     => Owned(f.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string())
+    }
+}
+
+#[test]
+fn test_from_cell() {
+    assert_eq!(Value::from(&Cell::Integer(1)), Integer(1));
+    assert_eq!(Value::from(&Cell::Str("Hej".to_string())), Str("Hej".to_string()));
+    assert_eq!(Value::from(&Cell::Qexpr(vec![Cell::Nil, Cell::Integer(1)])), Qexpr(vec![Nil, Integer(1)]));
+}
+
+#[test]
+fn test_fmt() {
+    assert_eq!(format!("{}", &Nil), "()");
+    assert_eq!(format!("{}", &Float(5.54321012)), "5.54");
+    assert_eq!(format!("{}", &Char('a')), "'a'");
+    assert_eq!(format!("{}", &Sexpr(vec![Nil, Integer(1)])), "(() 1)");
+    assert_eq!(format!("{}", &Qexpr(Vec::new())), "{}");
+    assert_eq!(format!("{}", &Function("func: (head {Any Any...})".to_string())), "func: (head {Any Any...})");
+}
@@ -0,0 +1,157 @@
+use cell::{Cell, Span, Tail};
+use compiler::{Chunk, Op};
+use environment::Environment;
+use super::{argument_error, call, dispatch, force, special_form};
+
+#[cfg(test)]
+use compiler;
+
+// Runs a compiled lambda body in the environment holding its arguments.
+// Forms in tail position hand their tail back to force and move its location
+// along, the same way the evaluator does for Tail::Eval.
+pub fn run(env: Environment, chunk: &Chunk, location: &mut Span) -> Tail {
+    let mut stack: Vec<Cell> = Vec::new();
+    let mut pc = 0;
+
+    loop {
+        let op = chunk.ops[pc];
+        pc += 1;
+
+        match op {
+            Op::Const(i)  => stack.push(chunk.constants[i].clone()),
+            Op::Local(i)  => stack.push(match env.argument(i) {
+                Some(c) => c,
+                None    => env.lookup(&chunk.arguments[i]),
+            }),
+            Op::Lookup(i) => stack.push(match chunk.constants[i] {
+                Cell::Symbol(ref s) => env.lookup(s),
+                _                   => internal_error(),
+            }),
+            Op::Apply { form, end, tail } => {
+                let (args, span) = parts(&chunk.constants[form]);
+
+                if tail {
+                    relocate(location, span);
+                }
+
+                let special = match stack.last() {
+                    Some(procedure) => special_form(env.clone(), procedure, args, if tail { &*location } else { span }),
+                    None            => internal_error(),
+                };
+
+                if let Some(t) = special {
+                    stack.pop();
+
+                    if tail {
+                        return t;
+                    }
+
+                    stack.push(force(t, span.clone()));
+                    pc = end;
+                }
+            },
+            Op::If(form) => {
+                let (args, span) = parts(&chunk.constants[form]);
+                relocate(location, span);
+
+                let procedure = match stack.pop() {
+                    Some(procedure) => procedure,
+                    None            => internal_error(),
+                };
+
+                let inline = match procedure {
+                    Cell::Builtin(builtin) => builtin.name == "if",
+                    _                      => false,
+                };
+
+                if !inline {
+                    return dispatch(env, &procedure, args, location);
+                }
+            },
+            Op::Branch(target) => match stack.pop() {
+                Some(Cell::Bool(true))  => (),
+                Some(Cell::Bool(false)) => pc = target,
+                Some(c @ Cell::Error(_)) => return Tail::Return(c),
+                Some(c) => return Tail::Return(Cell::error(format!("if, condition is of type {} expected Bool", c.get_type()))),
+                None    => internal_error(),
+            },
+            Op::Jump(target) => pc = target,
+            Op::Call { argc, form, tail } => {
+                let (_, span) = parts(&chunk.constants[form]);
+                let base = stack.len() - argc;
+                let args = stack.split_off(base);
+
+                let procedure = match stack.pop() {
+                    Some(procedure) => procedure,
+                    None            => internal_error(),
+                };
+
+                let t = match argument_error(&procedure, &args[..]) {
+                    Some(e) => Tail::Return(e.clone()),
+                    None    => call(env.clone(), &procedure, args, if tail { &*location } else { span }),
+                };
+
+                if tail {
+                    return t;
+                }
+
+                stack.push(force(t, span.clone()));
+            },
+            Op::Return => return match stack.pop() {
+                Some(c) => Tail::Return(c),
+                None    => internal_error(),
+            },
+        }
+    }
+}
+
+fn parts(form: &Cell) -> (&[Cell], &Span) {
+    match form {
+        &Cell::Sexpr(ref v, ref span) if !v.is_empty() => (&v[1..], span),
+        _                                              => internal_error(),
+    }
+}
+
+fn relocate(location: &mut Span, span: &Span) {
+    if span.is_some() {
+        *location = span.clone();
+    }
+}
+
+fn internal_error() -> ! {
+    panic!("Internal vm error");
+}
+
+#[test]
+fn test_run() {
+    let env = Environment::new();
+    let x = Cell::Symbol("x".to_string());
+    let body = [Cell::Symbol("if".to_string()),
+                Cell::Sexpr(vec![Cell::Symbol("==".to_string()), x.clone(), Cell::Integer(0)], None),
+                Cell::Qexpr(vec![Cell::Symbol("+".to_string()), x.clone(), Cell::Integer(1)]),
+                x.clone()];
+
+    let chunk = compiler::compile(&env, &[x.clone()], &body).unwrap();
+    let arguments = |c: &compiler::Chunk, v: Cell| env.make_argument_environment(c.arguments.clone(), vec![v]);
+
+    assert_eq!(force(run(arguments(&chunk, Cell::Integer(0)), &chunk, &mut None), None), Cell::Integer(1));
+    assert_eq!(force(run(arguments(&chunk, Cell::Integer(5)), &chunk, &mut None), None), Cell::Integer(5));
+
+    let chunk = compiler::compile(&env, &[x.clone()], &[Cell::Symbol("if".to_string()), x.clone(), Cell::Integer(1), Cell::Integer(2)]).unwrap();
+
+    assert_eq!(force(run(arguments(&chunk, Cell::Integer(3)), &chunk, &mut None), None),
+               Cell::error("if, condition is of type Integer expected Bool".to_string()));
+
+    let set = Cell::Sexpr(vec![Cell::Symbol("set!".to_string()), Cell::Qexpr(vec![x.clone()]), Cell::Integer(2)], None);
+    let chunk = compiler::compile(&env, &[x.clone()], &[Cell::Symbol("list".to_string()), set, x.clone()]).unwrap();
+
+    assert_eq!(force(run(arguments(&chunk, Cell::Integer(1)), &chunk, &mut None), None),
+               Cell::Qexpr(vec![Cell::Nil, Cell::Integer(2)]));
+
+    // Without arguments in position the names are looked up
+    let sub_env = env.make_sub_environment();
+    sub_env.insert("x", &Cell::Integer(5));
+
+    assert_eq!(force(run(sub_env, &chunk, &mut None), None),
+               Cell::Qexpr(vec![Cell::Nil, Cell::Integer(2)]));
+}
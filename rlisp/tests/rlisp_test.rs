@@ -1,6 +1,14 @@
 extern crate rlisp;
 
-use self::rlisp::Rlisp;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
+
+use self::rlisp::{Cell, ErrorKind, IntoCell, Limits, Rlisp, RlispError, THREAD_DEPTH, Type, Value};
 
 #[test]
 fn test_rlisp() {
@@ -186,4 +194,789 @@ fn test_comments() {
                        (list (head l))}})
     "#), "()");
     assert_eq!(rlisp.execute("(rev {1 2 3})"), "{3 2 1}");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_eval_str() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.eval_str("(+ 1 2)"), Ok(Value::Integer(3)));
+    assert_eq!(rlisp.eval_str("{1 \"a\" 'b'}"),
+               Ok(Value::Qexpr(vec![Value::Integer(1), Value::Str("a".to_string()), Value::Char('b')])));
+
+    let e = rlisp.eval_str("(/ 1 0)").unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Runtime);
+    assert_eq!(e.message, "/, 2, can't divide by zero");
+
+    let e = rlisp.eval_str("(+ 1 2").unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Parse);
+    assert_eq!(e.location.unwrap().line, 1);
+}
+
+#[test]
+fn test_tail_calls() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {count n acc} {if (== n 0) acc {count (- n 1) (+ acc 1)}})"), "()");
+    assert_eq!(rlisp.execute("(count 100000 0)"), "100000");
+
+    assert_eq!(rlisp.execute("(def {rev-acc l acc} {if (== l {}) acc {rev-acc (tail l) (join (list (head l)) acc)}})"), "()");
+    assert_eq!(rlisp.execute("(rev-acc {1 2 3} {})"), "{3 2 1}");
+
+    assert_eq!(rlisp.execute("(def {range n acc} {if (== n 0) acc {range (- n 1) (join (list n) acc)}})"), "()");
+    assert_eq!(rlisp.execute("(len (rev-acc (range 5000 {}) {}))"), "5000");
+
+    assert_eq!(rlisp.execute("(def {count-eval n} {if (== n 0) 0 {eval {count-eval (- n 1)}}})"), "()");
+    assert_eq!(rlisp.execute("(count-eval 100000)"), "0");
+}
+
+#[test]
+fn test_closure_chain() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {mk a} {lambda {b} {lambda {c} {+ a (+ b c)}}})"), "()");
+    assert_eq!(rlisp.execute("(def {f} ((mk 1) 2))"), "()");
+    rlisp.collect_garbage();
+    assert_eq!(rlisp.execute("(f 3)"), "6");
+
+    assert_eq!(rlisp.execute("(def {add4 a b c d} {+ a (+ b (+ c d))})"), "()");
+    assert_eq!(rlisp.execute("(def {g} (((add4 1) 2) 3))"), "()");
+    rlisp.collect_garbage();
+    assert_eq!(rlisp.execute("(g 4)"), "10");
+}
+
+#[test]
+fn test_deep_closure_chain() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {wrap f n} {if (== n 0) f {wrap (lambda {x} {f (+ x 1)}) (- n 1)}})"), "()");
+    assert_eq!(rlisp.execute("(def {h} (wrap (lambda {x} {+ x 0}) 500))"), "()");
+    rlisp.collect_garbage();
+    assert_eq!(rlisp.execute("(h 0)"), "500");
+}
+
+#[test]
+fn test_collect_cycles() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {mk x} {(lambda {_} {head (list self)}) (set! {self} (lambda {} {+ x 0}))})"), "()");
+    assert_eq!(rlisp.execute("(def {c} (mk 5))"), "()");
+    assert_eq!(rlisp.collect_garbage(), 0);
+    assert_eq!(rlisp.execute("(c)"), "5");
+
+    assert_eq!(rlisp.execute("(def {c} 0)"), "()");
+    assert_eq!(rlisp.collect_garbage(), 1);
+}
+
+#[test]
+fn test_error_locations() {
+    let mut rlisp = Rlisp::new();
+
+    let e = rlisp.eval_str("(+ 1\n   (undefined 2))").unwrap_err();
+    assert_eq!(e.message, "Undefined symbol: undefined");
+    let l = e.location.unwrap();
+    assert_eq!((&l.file[..], l.line, l.column), ("<input>", 2, 4));
+
+    let e = rlisp.eval_str("  (+ 1 'a')").unwrap_err();
+    assert_eq!(e.message, "+, argument 2 is of type Char expected Integer|Float...");
+    let l = e.location.unwrap();
+    assert_eq!((l.line, l.column), (1, 3));
+
+    assert_eq!(rlisp.execute("(def {f x} {+ x (/ x 0)})"), "()");
+    let e = rlisp.eval_str("(list 1\n      (f 1))").unwrap_err();
+    assert_eq!(e.message, "/, 2, can't divide by zero");
+    let l = e.location.unwrap();
+    assert_eq!((l.line, l.column), (1, 17));
+}
+
+#[test]
+fn test_backtrace() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {inner x} {/ x 0})"), "()");
+    assert_eq!(rlisp.execute("(def {outer x} {+ 1 (inner x)})"), "()");
+    assert_eq!(rlisp.execute("(def {tail x} {inner x})"), "()");
+
+    let names = |e: RlispError| e.backtrace.iter().map(|f| f.name.clone()).collect::<Vec<Option<String>>>();
+
+    let e = rlisp.eval_str("(outer 5)").unwrap_err();
+    assert_eq!(e.backtrace[2].location.as_ref().map(|l| (l.line, l.column)), Some((1, 1)));
+    assert_eq!(names(e), vec![Some("/".to_string()), Some("inner".to_string()), Some("outer".to_string())]);
+
+    let e = rlisp.eval_str("(tail 5)").unwrap_err();
+    assert_eq!(names(e), vec![Some("/".to_string()), Some("inner".to_string())]);
+
+    let e = rlisp.eval_str("((lambda {x} {/ x 0}) 1)").unwrap_err();
+    assert_eq!(names(e), vec![Some("/".to_string()), None]);
+
+    assert_eq!(rlisp.execute("(def {anon} (lambda {x} {head x}))"), "()");
+    let e = rlisp.eval_str("(anon 1)").unwrap_err();
+    assert_eq!(names(e), vec![Some("head".to_string()), Some("anon".to_string())]);
+
+    let e = rlisp.eval_str("(undefined 1)").unwrap_err();
+    assert!(e.backtrace.is_empty());
+}
+
+#[test]
+fn test_feed() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.feed("(def {a} 1)\n(+ a\n"), vec![Ok(Value::Nil)]);
+    assert!(rlisp.is_pending());
+
+    assert_eq!(rlisp.feed("   2) (+ a 3)\n"), vec![Ok(Value::Integer(3)), Ok(Value::Integer(4))]);
+    assert!(!rlisp.is_pending());
+
+    let results = rlisp.feed("(+ 1 2))\n");
+    assert_eq!(results[0], Ok(Value::Integer(3)));
+    let e = results[1].clone().unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Parse);
+    assert_eq!(e.location.map(|l| (l.line, l.column)), Some((4, 8)));
+
+    let results = rlisp.feed("(+ 1 #x) (+ 2 3)\n");
+    assert_eq!(results[0].clone().unwrap_err().kind, ErrorKind::Parse);
+    assert_eq!(results[1], Ok(Value::Integer(5)));
+
+    assert_eq!(rlisp.feed("(def {b} 2) b"), vec![Ok(Value::Nil)]);
+    assert_eq!(rlisp.finish(), vec![Ok(Value::Integer(2))]);
+    assert!(!rlisp.is_pending());
+
+    assert_eq!(rlisp.eval_str("\"a\0b\""), Ok(Value::Str("a\0b".to_string())));
+}
+
+#[test]
+fn test_execute_all() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {a} 1) (+ a 1)"), "2");
+    assert_eq!(rlisp.execute(""), "()");
+
+    assert_eq!(rlisp.execute_all("(def {b} 2)\n(+ a b)\nb"),
+               vec![Ok(Value::Nil), Ok(Value::Integer(3)), Ok(Value::Integer(2))]);
+
+    let results = rlisp.execute_all("(def {c} 3) (undefined) (def {d} 4)");
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].clone().unwrap_err().message, "Undefined symbol: undefined");
+    assert_eq!(rlisp.execute("c"), "3");
+    assert_eq!(rlisp.execute("d"), "Error: Undefined symbol: d");
+
+    let results = rlisp.execute_all("(def {e} 5) (+ 1");
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].clone().unwrap_err().kind, ErrorKind::Parse);
+    assert_eq!(rlisp.execute("e"), "Error: Undefined symbol: e");
+}
+
+#[test]
+fn test_string_escapes() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.eval_str(r#""a\n\"b\"\u{e5}""#), Ok(Value::Str("a\n\"b\"å".to_string())));
+    assert_eq!(rlisp.eval_str(r"'\t'"), Ok(Value::Char('\t')));
+
+    let printed = rlisp.execute(r#"{"tab\there" '\\' "q\"uote"}"#);
+    assert_eq!(printed, r#"{"tab\there" '\\' "q\"uote"}"#);
+    assert_eq!(rlisp.execute(&printed[..]), printed);
+}
+
+#[test]
+fn test_types() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(type-of 1)"), ":Integer");
+    assert_eq!(rlisp.execute("(type-of {1 'a'})"), ":{Integer Char}");
+    assert_eq!(rlisp.execute("(type-of :Integer)"), ":Type");
+    assert_eq!(rlisp.execute(":{Integer|Float...}"), ":{Integer|Float...}");
+    assert_eq!(rlisp.execute("(is-type 1.5 :Integer|Float)"), "#t");
+    assert_eq!(rlisp.execute("(is-type {1 2 3} :{Integer...})"), "#t");
+    assert_eq!(rlisp.execute("(is-type {1 \"a\"} :{Integer...})"), "#f");
+    assert_eq!(rlisp.execute("(is-type {a 1} :{Symbol [Integer]})"), "#t");
+    assert_eq!(rlisp.execute("(== (type-of 1) :Integer)"), "#t");
+    assert_eq!(rlisp.eval_str(":Integer"), Ok(Value::Type("Integer".to_string())));
+    assert_eq!(rlisp.execute("(is-type 1 2)"), "Error: is-type, argument 2 is of type Integer expected Type");
+}
+
+#[test]
+fn test_lambda_argument_types() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {add-int a :Integer b :Integer} {+ a b})"), "()");
+    assert_eq!(rlisp.execute("(add-int 1 2)"), "3");
+    assert_eq!(rlisp.execute("(add-int 1 2.0)"), "Error: add-int, argument 2 is of type Float expected Integer");
+    assert_eq!(rlisp.execute("(add-int 1 2 3)"), "Error: add-int, requiers 2 arguments, got 3");
+    assert_eq!(rlisp.execute("(add-int 1.0)"), "Error: add-int, argument 1 is of type Float expected Integer");
+    assert_eq!(rlisp.execute("((add-int 1) 2)"), "3");
+    assert_eq!(rlisp.execute("(add-int 1)"), "(lambda {b :Integer} {+ a b})");
+
+    assert_eq!(rlisp.execute("(def {num x :Integer|Float} {list x})"), "()");
+    assert_eq!(rlisp.execute("(num 1.5)"), "{1.50}");
+    assert_eq!(rlisp.execute("(num 'a')"), "Error: num, argument 1 is of type Char expected Integer|Float");
+
+    assert_eq!(rlisp.execute("(def {opt a b :[Integer]} {list a b})"), "()");
+    assert_eq!(rlisp.execute("(opt 1)"), "{1 ()}");
+    assert_eq!(rlisp.execute("(opt 1 2)"), "{1 2}");
+    assert_eq!(rlisp.execute("(opt 1 'b')"), "Error: opt, argument 2 is of type Char expected [Integer]");
+
+    assert_eq!(rlisp.execute("(def {sum first :Integer rest :Integer...} {unpack + (join (list first) rest)})"), "()");
+    assert_eq!(rlisp.execute("(sum 1 2 3)"), "6");
+    assert_eq!(rlisp.execute("(sum 1)"), "1");
+    assert_eq!(rlisp.execute("(sum 1 2 'c')"), "Error: sum, argument 3 is of type Char expected Integer...");
+
+    assert_eq!(rlisp.execute("((lambda {x :String} {x}) 1)"), "Error: lambda, argument 1 is of type Integer expected String");
+
+    assert_eq!(rlisp.execute("(def {bad ... x} {x})"), "Error: def, ... has to be the last argument");
+    assert_eq!(rlisp.execute("(lambda {:Integer x} {x})"), "Error: lambda, type annotation :Integer has to follow an argument");
+    assert_eq!(rlisp.execute("(def {bad a :[Integer] b} {b})"), "Error: def, b can't follow an optional argument");
+}
+
+#[test]
+fn test_maps() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {m} #{\"name\" \"rlisp\" age 3})"), "()");
+    assert_eq!(rlisp.execute("m"), "#{\"name\" \"rlisp\" age 3}");
+    assert_eq!(rlisp.execute("(get m \"name\")"), "\"rlisp\"");
+    assert_eq!(rlisp.execute("(get m \"missing\")"), "()");
+    assert_eq!(rlisp.execute("(get m \"missing\" 0)"), "0");
+    assert_eq!(rlisp.execute("(assoc m 1 (+ 1 2) 'c' {x})"), "#{1 3 'c' {x} \"name\" \"rlisp\" age 3}");
+    assert_eq!(rlisp.execute("(dissoc m \"name\")"), "#{age 3}");
+    assert_eq!(rlisp.execute("(keys m)"), "{\"name\" age}");
+    assert_eq!(rlisp.execute("(values m)"), "{\"rlisp\" 3}");
+    assert_eq!(rlisp.execute("(contains m \"name\")"), "#t");
+    assert_eq!(rlisp.execute("(merge m #{age 4} #{1 2})"), "#{1 2 \"name\" \"rlisp\" age 4}");
+    assert_eq!(rlisp.execute("(== m #{age 3 \"name\" \"rlisp\"})"), "#t");
+    assert_eq!(rlisp.execute("(== m #{})"), "#f");
+    assert_eq!(rlisp.execute("(type-of m)"), ":Map");
+    assert_eq!(rlisp.execute("(get m 1.5)"), "Error: get, argument 2 is of type Float expected Bool|Integer|Char|String|Symbol");
+    assert_eq!(rlisp.execute("(get {1} 1)"), "Error: get, argument 1 is of type {Integer} expected Map");
+
+    let printed = rlisp.execute("(assoc m \"nested\" #{\"a\\n\" {1 2}})");
+    assert_eq!(rlisp.execute(&printed[..]), printed);
+
+    assert_eq!(rlisp.eval_str("#{1 'a'}"), Ok(Value::Map(vec![(Value::Integer(1), Value::Char('a'))])));
+}
+
+#[test]
+fn test_higher_order_builtins() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {add a b} {+ a b})"), "()");
+    assert_eq!(rlisp.execute("(def {inc-all} (map (add 1)))"), "()");
+    assert_eq!(rlisp.execute("(inc-all {1 2 3})"), "{2 3 4}");
+    assert_eq!(rlisp.execute("(map (lambda {x} {* x x}) {1 2 3})"), "{1 4 9}");
+    assert_eq!(rlisp.execute("(filter (lambda {x} {> x 1}) {1 2 3})"), "{2 3}");
+    assert_eq!(rlisp.execute("(fold + 0 {1 2 3 4})"), "10");
+    assert_eq!(rlisp.execute("(fold (lambda {acc x} {join acc (list x x)}) {} {1 2})"), "{1 1 2 2}");
+    assert_eq!(rlisp.execute("(nth 1 {a b c})"), "b");
+    assert_eq!(rlisp.execute("(def {first} (nth 0))"), "()");
+    assert_eq!(rlisp.execute("(first {5 6})"), "5");
+    assert_eq!(rlisp.execute("(nth 3 {a b c})"), "Error: nth, index 3 out of range for list of length 3");
+
+    assert_eq!(rlisp.execute("(map head {{1} {}})"), "Error: head, argument list at 1: requiers 1+ arguments, got 0");
+    assert_eq!(rlisp.execute("(filter + {1 2})"), "Error: filter, predicate returned Integer expected Bool");
+    assert_eq!(rlisp.execute("(map 1 {1 2})"), "Error: map, argument 1 is of type Integer expected Builtin|Lambda");
+
+    assert_eq!(rlisp.execute("(def {range n acc} {if (== n 0) acc {range (- n 1) (join (list n) acc)}})"), "()");
+    assert_eq!(rlisp.execute("(len (map (add 1) (range 20000 {})))"), "20000");
+    assert_eq!(rlisp.execute("(fold + 0 (range 20000 {}))"), "200010000");
+}
+
+#[test]
+fn test_strings() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute(r#"(str-len "håll")"#), "4");
+    assert_eq!(rlisp.execute(r#"(str-slice "håll" 1 3)"#), r#""ål""#);
+    assert_eq!(rlisp.execute(r#"(str-slice "håll" 2)"#), r#""ll""#);
+    assert_eq!(rlisp.execute(r#"(str-slice "håll" 3 1)"#), "Error: str-slice, index 1 out of range for string of length 4");
+    assert_eq!(rlisp.execute(r#"(str-concat "å" "b" "c")"#), r#""åbc""#);
+    assert_eq!(rlisp.execute(r#"(str-split "a,b,c" ",")"#), r#"{"a" "b" "c"}"#);
+    assert_eq!(rlisp.execute(r#"(str-join "-" (str-split "a,b,c" ","))"#), r#""a-b-c""#);
+    assert_eq!(rlisp.execute(r#"(str-find "åäö" "ö")"#), "2");
+    assert_eq!(rlisp.execute(r#"(str-find "åäö" "x")"#), "()");
+    assert_eq!(rlisp.execute(r#"(str-replace "a.b.c" "." "ö")"#), r#""aöböc""#);
+    assert_eq!(rlisp.execute(r#"(str-trim "  å  ")"#), r#""å""#);
+    assert_eq!(rlisp.execute(r#"(str-upper "åbc")"#), r#""ÅBC""#);
+    assert_eq!(rlisp.execute(r#"(str-lower "ÅBC")"#), r#""åbc""#);
+    assert_eq!(rlisp.execute(r#"(starts-with "åbc" "åb")"#), "#t");
+    assert_eq!(rlisp.execute(r#"(ends-with "åbc" "åb")"#), "#f");
+    assert_eq!(rlisp.execute(r#"(str->list "åb")"#), "{'å' 'b'}");
+    assert_eq!(rlisp.execute("(list->str {'å' 'b'})"), r#""åb""#);
+    assert_eq!(rlisp.execute("(list->str {'å' 1})"), "Error: list->str, argument list at 1: argument 2 is of type Integer expected Char...");
+}
+
+#[test]
+fn test_files() {
+    let mut rlisp = Rlisp::new();
+    let path = env::temp_dir().join("rlisp_test_files.txt");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(rlisp.execute(&format!("(def {{path}} {:?})", path)), "()");
+    assert_eq!(rlisp.execute("(spit path \"ett\\ntvå\\n\")"), "()");
+    assert_eq!(rlisp.execute("(def {f} (open path \"a\"))"), "()");
+    assert_eq!(rlisp.execute("(write f \"tre\")"), "()");
+    assert_eq!(rlisp.execute("(close f)"), "()");
+    assert_eq!(rlisp.execute("(write f \"fyra\")"), "Error: write, file is closed");
+    assert_eq!(rlisp.execute("(type-of f)"), ":File");
+
+    assert_eq!(rlisp.execute("(def {f} (open path))"), "()");
+    assert_eq!(rlisp.execute("(read-line f)"), "\"ett\"");
+    assert_eq!(rlisp.execute("(read-all f)"), "\"två\\ntre\"");
+    assert_eq!(rlisp.execute("(read-line f)"), "()");
+    assert_eq!(rlisp.execute("(close f)"), "()");
+    assert_eq!(rlisp.execute("(str-split (slurp path) \"\\n\")"), "{\"ett\" \"två\" \"tre\"}");
+
+    assert_eq!(rlisp.execute("(open path \"rw\")"), "Error: open, unknown mode \"rw\" expected \"r\", \"w\" or \"a\"");
+    assert!(rlisp.execute("(slurp \"/nonexistent/rlisp.txt\")").starts_with("Error: slurp, /nonexistent/rlisp.txt: "));
+}
+
+#[test]
+fn test_load() {
+    let dir = env::temp_dir().join("rlisp_test_load");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+
+    let write = |name: &str, source: &str| {
+        File::create(dir.join(name)).unwrap().write_all(source.as_bytes()).unwrap();
+    };
+
+    write("main.rl", "(load \"lib/util.rl\")\n(def {x} (double 21))\nx\n");
+    write("lib/util.rl", "; helpers\n(def {double n} {* 2 n})\n(load \"more.rl\")\n");
+    write("lib/more.rl", "(def {triple n} {* 3 n})\n");
+    write("a.rl", "(load \"b.rl\")\n");
+    write("b.rl", "(load \"a.rl\")\n");
+    write("runtime.rl", "(def {y} 1)\n(+ y \"a\")\n(def {z} 2)\n");
+    write("parse.rl", "(def {w} 1)\n(+ 1\n");
+
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.load_file(dir.join("main.rl")), Ok(Value::Integer(42)));
+    assert_eq!(rlisp.execute("(triple x)"), "126");
+    assert_eq!(rlisp.execute(&format!("(load {:?})", dir.join("lib/more.rl").to_str().unwrap())), "()");
+
+    let e = rlisp.load_file(dir.join("a.rl")).unwrap_err();
+    assert!(e.message.starts_with("load, cycle detected: "));
+    assert!(e.message.ends_with("a.rl"));
+    assert_eq!(rlisp.load_file(dir.join("lib/util.rl")), Ok(Value::Nil));
+
+    let e = rlisp.load_file(dir.join("runtime.rl")).unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Runtime);
+    assert_eq!(e.location.unwrap().line, 2);
+    assert_eq!(rlisp.execute("y"), "1");
+    assert_eq!(rlisp.execute("z"), "Error: Undefined symbol: z");
+
+    let e = rlisp.load_file(dir.join("parse.rl")).unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Parse);
+    assert_eq!(rlisp.execute("w"), "Error: Undefined symbol: w");
+
+    let e = rlisp.eval_str(&format!("(load {:?})", dir.join("parse.rl").to_str().unwrap())).unwrap_err();
+    assert_eq!(e.message, "unclosed '('");
+    let location = e.location.unwrap();
+    assert!(location.file.ends_with("parse.rl"));
+    assert_eq!((location.line, location.column), (2, 1));
+
+    assert!(rlisp.execute("(load \"/nonexistent/rlisp.rl\")").starts_with("Error: load, /nonexistent/rlisp.rl: "));
+}
+
+#[test]
+fn test_modules() {
+    let dir = env::temp_dir().join("rlisp_test_modules");
+    fs::create_dir_all(&dir).unwrap();
+
+    let write = |name: &str, source: &str| {
+        File::create(dir.join(name)).unwrap().write_all(source.as_bytes()).unwrap();
+    };
+
+    write("math.rl", "(module {math} {square cube})\n(def {helper x y} {* x y})\n(def {square x} {helper x x})\n(def {cube x} {* x (square x)})\n");
+    write("geometry.rl", "(module {geometry} {area})\n(import \"math.rl\" {square})\n(def {area r} {* 3 (square r)})\n");
+    write("plain.rl", "(def {plain} 1)\n");
+    write("missing.rl", "(module {missing} {nothing})\n");
+    write("leaky.rl", "(module {leaky} {shared})\n");
+
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute(&format!("(import {:?})", dir.join("math.rl").to_str().unwrap())), "()");
+    assert_eq!(rlisp.execute("(math/square 4)"), "16");
+    assert_eq!(rlisp.execute("(math/cube 2)"), "8");
+    assert_eq!(rlisp.execute("math"), "Error: Undefined symbol: math");
+    assert_eq!(rlisp.execute("(def {math} 1)"), "()");
+    assert_eq!(rlisp.execute("(math/square 3)"), "9");
+    assert_eq!(rlisp.execute("(def {table} #{a 1})"), "()");
+    assert_eq!(rlisp.execute("table/a"), "Error: Undefined symbol: table/a");
+    assert_eq!(rlisp.execute("helper"), "Error: Undefined symbol: helper");
+    assert_eq!(rlisp.execute("square"), "Error: Undefined symbol: square");
+    assert_eq!(rlisp.execute("math/helper"), "Error: math does not export helper");
+    assert_eq!(rlisp.execute("nomod/helper"), "Error: Undefined symbol: nomod/helper");
+    assert_eq!(rlisp.execute("(/ 6 2)"), "3");
+
+    assert_eq!(rlisp.execute(&format!("(import {:?} {{area}})", dir.join("geometry.rl").to_str().unwrap())), "()");
+    assert_eq!(rlisp.execute("(area 2)"), "12");
+    assert_eq!(rlisp.execute("(geometry/area 1)"), "3");
+    assert_eq!(rlisp.execute("square"), "Error: Undefined symbol: square");
+
+    assert_eq!(rlisp.execute(&format!("(import {:?} {{volume}})", dir.join("geometry.rl").to_str().unwrap())),
+               "Error: import, geometry does not export volume");
+    assert!(rlisp.execute(&format!("(import {:?})", dir.join("plain.rl").to_str().unwrap())).ends_with("plain.rl does not declare a module"));
+    assert_eq!(rlisp.execute(&format!("(import {:?})", dir.join("missing.rl").to_str().unwrap())),
+               "Error: import, module missing does not define nothing");
+    assert_eq!(rlisp.execute("(def {shared} 1)"), "()");
+    assert_eq!(rlisp.execute(&format!("(import {:?})", dir.join("leaky.rl").to_str().unwrap())),
+               "Error: import, module leaky does not define shared");
+    assert_eq!(rlisp.execute("(module {top} {})"), "Error: module, can only be declared in an imported file");
+}
+
+#[test]
+fn test_macros() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {x} 5)"), "()");
+    assert_eq!(rlisp.execute("(when (> x 1) (def {y} 1) (+ x 1))"), "6");
+    assert_eq!(rlisp.execute("(when (> x 10) (undefined))"), "()");
+    assert_eq!(rlisp.execute("(unless (> x 10) x)"), "5");
+    assert_eq!(rlisp.execute("(let {{a 1} {b (+ x 1)}} (+ a b))"), "7");
+    assert_eq!(rlisp.execute("a"), "Error: Undefined symbol: a");
+    assert_eq!(rlisp.execute("(cond {(< x 3) \"small\"} {(< x 8) \"medium\"} {#t \"large\"})"), "\"medium\"");
+    assert_eq!(rlisp.execute("(cond {(< x 3) 1})"), "()");
+
+    assert_eq!(rlisp.execute("(defmacro {swap-args f a b} {f b a})"), "()");
+    assert_eq!(rlisp.execute("(swap-args - 1 10)"), "9");
+    assert_eq!(rlisp.execute("(macroexpand {swap-args - 1 (+ x 1)})"), "{- (+ x 1) 1}");
+    assert_eq!(rlisp.execute("(eval (macroexpand {swap-args - 1 (+ x 1)}))"), "5");
+    assert_eq!(rlisp.execute("(macroexpand {+ 1 2})"), "{+ 1 2}");
+    assert_eq!(rlisp.execute("(def {launched} #f)"), "()");
+    assert_eq!(rlisp.execute("(macroexpand {(def {launched} #t) 1})"), "{(def {launched} #t) 1}");
+    assert_eq!(rlisp.execute("launched"), "#f");
+    assert_eq!(rlisp.execute("(swap-args -)"), "Error: swap-args, requiers 3 arguments, got 1");
+    assert_eq!(rlisp.execute("(type-of swap-args)"), ":Macro");
+
+    assert_eq!(rlisp.execute("(defmacro {quote-both a b} {join {a} {b}})"), "()");
+    assert_eq!(rlisp.execute("(quote-both x (+ 1 2))"), "{x (+ 1 2)}");
+    assert_eq!(rlisp.execute("(defmacro {my-when c ...} {if c {begin ...} ()})"), "()");
+    assert_eq!(rlisp.execute("(my-when (> x 1) (def {z} 2) (+ x z))"), "7");
+
+    // Builtins are embedded as values so shadowing them at the call site doesn't change the expansion
+    assert_eq!(rlisp.execute("(defmacro {my-unless c x} {if c {} x})"), "()");
+    assert_eq!(rlisp.execute("(def {shadow if} {my-unless #f if})"), "()");
+    assert_eq!(rlisp.execute("(shadow 3)"), "3");
+
+    // Names bound by the template can't capture the callers variables
+    assert_eq!(rlisp.execute("(def {tmp} #t)"), "()");
+    assert_eq!(rlisp.execute("(defmacro {my-or a b} {let {{tmp a}} (if tmp tmp b)})"), "()");
+    assert_eq!(rlisp.execute("(my-or #f tmp)"), "#t");
+    assert_eq!(rlisp.execute("(defmacro {call-with f v} {(lambda {x} {f x}) v})"), "()");
+    assert_eq!(rlisp.execute("((lambda {x} {call-with (lambda {y} {+ x y}) 1}) 10)"), "11");
+
+    // Only the names inside the scope of the binding are renamed
+    assert_eq!(rlisp.execute("(defmacro {shadowed v} {list x (let {{x v}} x)})"), "()");
+    assert_eq!(rlisp.execute("(shadowed 1)"), "{5 1}");
+    assert_eq!(rlisp.execute("(defmacro {twice v} {let* {{tmp v} {tmp (* tmp 2)}} tmp})"), "()");
+    assert_eq!(rlisp.execute("(twice 4)"), "8");
+
+    // Macros are templates, their bodies are not evaluated when expanding
+    assert_eq!(rlisp.execute("(gensym)"), "Error: Undefined symbol: gensym");
+}
+
+#[test]
+fn test_special_forms() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(and #f (undefined))"), "#f");
+    assert_eq!(rlisp.execute("(or #t (undefined))"), "#t");
+    assert_eq!(rlisp.execute("(and #t (undefined))"), "Error: Undefined symbol: undefined");
+    assert_eq!(rlisp.execute("(and #t 1)"), "Error: and, condition is of type Integer expected Bool");
+
+    assert_eq!(rlisp.execute("(if #t (+ 1 1) (undefined))"), "2");
+    assert_eq!(rlisp.execute("(if 1 2 3)"), "Error: if, condition is of type Integer expected Bool");
+
+    assert_eq!(rlisp.execute("(begin (def {x} 1) (set! {x} 2) (+ x 1))"), "3");
+    assert_eq!(rlisp.execute("(begin)"), "()");
+
+    assert_eq!(rlisp.execute("(when (> x 1) x)"), "2");
+    assert_eq!(rlisp.execute("(unless (> x 1) (undefined))"), "()");
+
+    assert_eq!(rlisp.execute("(cond {(== x 1) 'a'} {(== x 2) 'b'} {#t 'c'})"), "'b'");
+    assert_eq!(rlisp.execute("(cond {(== x 1) 'a'})"), "()");
+    assert_eq!(rlisp.execute("(cond {(== x 2)})"), "#t");
+
+    assert_eq!(rlisp.execute("(let {{x 10} {y x}} (+ x y))"), "12");
+    assert_eq!(rlisp.execute("(let* {{x 10} {y x}} (+ x y))"), "20");
+    assert_eq!(rlisp.execute("(let {{x 10}} (def {z} x) x)"), "10");
+    assert_eq!(rlisp.execute("z"), "10");
+    assert_eq!(rlisp.execute("x"), "2");
+    assert_eq!(rlisp.execute("(let {{x}} x)"), "Error: let, argument list at 1: argument 1 is of type {Symbol} expected {Symbol Any}...");
+
+    assert_eq!(rlisp.execute("(def {loop n} {when (> n 0) (loop (- n 1))})"), "()");
+    assert_eq!(rlisp.execute("(loop 100000)"), "()");
+    assert_eq!(rlisp.execute("(def {countdown n} {cond {(== n 0) \"done\"} {#t (countdown (- n 1))}})"), "()");
+    assert_eq!(rlisp.execute("(countdown 100000)"), "\"done\"");
+
+    assert_eq!(rlisp.execute("(map begin {undefined})"), "Error: begin, special form can't be applied");
+    assert_eq!(rlisp.execute("(fold and #t {#t #f})"), "Error: and, special form can't be applied");
+}
+
+#[test]
+fn test_errors() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(error \"bad input\" {1 2})"), "Error: bad input");
+    assert_eq!(rlisp.execute("(raise 42)"), "Error: 42");
+    assert_eq!(rlisp.execute("(try (/ 1 0) (catch e (error-message e)))"), "\"/, 2, can't divide by zero\"");
+    assert_eq!(rlisp.execute("(try (error \"bad\" {1 2}) (catch e (payload e)))"), "{1 2}");
+    assert_eq!(rlisp.execute("(try (raise #{\"code\" 7}) (catch e (get (payload e) \"code\")))"), "7");
+    assert_eq!(rlisp.execute("(try (+ 1 2) (catch e 0))"), "3");
+    assert_eq!(rlisp.execute("(try (undefined) (catch e (error? e)))"), "#t");
+    assert_eq!(rlisp.execute("(error? (+ 1 2))"), "#f");
+    assert_eq!(rlisp.execute("(error? (head {}))"), "#t");
+    assert_eq!(rlisp.execute("(payload (error \"bad\"))"), "()");
+    assert_eq!(rlisp.execute("(error-message 1)"), "Error: error-message, argument 1 is of type Integer expected Error");
+    assert_eq!(rlisp.execute("(filter error? {undefined})"), "{}");
+
+    assert_eq!(rlisp.execute("(try (raise 1) (catch e (raise e)))"), "Error: 1");
+    assert_eq!(rlisp.execute("(try (raise 1) (oops e 2))"), "Error: try, expected (catch name handler...) as argument 2");
+
+    assert_eq!(rlisp.execute("(def {safe-div a b} {try (/ a b) (catch e 0)})"), "()");
+    assert_eq!(rlisp.execute("(safe-div 6 3)"), "2");
+    assert_eq!(rlisp.execute("(safe-div 6 0)"), "0");
+    assert_eq!(rlisp.execute("(def {parse-age x} {if (is-type x :Integer) x (error \"not an age\" x)})"), "()");
+    assert_eq!(rlisp.execute("(try (parse-age \"old\") (catch e (str-concat (error-message e) \": \" (payload e))))"),
+               "\"not an age: old\"");
+}
+
+#[test]
+fn test_continuations() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {+ 1 2}))"), "3");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {+ 1 (k 10)}))"), "10");
+    assert_eq!(rlisp.execute("(+ 1 (call/cc (lambda {k} {k 1})))"), "2");
+    assert!(rlisp.execute("(call/cc (lambda {k} {begin k}))").starts_with("continuation: "));
+
+    // Early return from deep recursion
+    assert_eq!(rlisp.execute("(def {find-first p l} {call/cc (lambda {return} {begin (map (lambda {x} {when (p x) (return x)}) l) ()})})"), "()");
+    assert_eq!(rlisp.execute("(find-first (lambda {x} {> x 2}) {1 2 3 4})"), "3");
+    assert_eq!(rlisp.execute("(find-first (lambda {x} {> x 5}) {1 2 3 4})"), "()");
+    assert_eq!(rlisp.execute("(def {walk k n} {if (== n 0) (k \"bottom\") (walk k (- n 1))})"), "()");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {walk k 10000}))"), "\"bottom\"");
+
+    // An inner escape can reach an outer call/cc, and try doesn't catch escapes
+    assert_eq!(rlisp.execute("(call/cc (lambda {outer} {+ 1 (call/cc (lambda {inner} {outer 5}))}))"), "5");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {try (k 1) (catch e 2)}))"), "1");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {error? (k 1)}))"), "1");
+
+    // Escape only, re-entering after call/cc has returned is an error
+    assert_eq!(rlisp.execute("(def {saved} (call/cc (lambda {k} {begin k})))"), "()");
+    assert_eq!(rlisp.execute("(saved 1)"), "Error: continuation can't be resumed after its call/cc has returned");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {k 1 2}))"), "Error: continuation, requiers 0 or 1 arguments, got 2");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {k 7}))"), "7");
+}
+
+#[test]
+fn test_compiled_matches_evaluator() {
+    let programs = [
+        "(def {fib n} {if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))})",
+        "(fib 15)",
+        "(def {count n acc} {if (== n 0) acc {count (- n 1) (+ acc 1)}})",
+        "(count 10000 0)",
+        "(def {sum ...} {fold + 0 ...})",
+        "(sum 1 2 3 4)",
+        "(def {adder x} {lambda {y} {+ x y}})",
+        "((adder 2) 3)",
+        "(def {add3 a b c} {+ a b c})",
+        "((add3 1 2) 3)",
+        "(def {safe-div a b} {try (/ a b) (catch e (error-message e))})",
+        "(safe-div 1 0)",
+        "(def {classify n} {cond {(< n 0) 'n'} {(== n 0) 'z'} {#t 'p'}})",
+        "(map classify {-1 0 1})",
+        "(def {swap-args f a b} {f b a})",
+        "(swap-args - 1 10)",
+        "(def {bad x} {if x 1 2})",
+        "(bad 1)",
+        "(def {undefined-call x} {+ x (nothing x)})",
+        "(undefined-call 1)",
+        "(def {set-local x} {begin (set! {x} 2) x})",
+        "(set-local 1)",
+        "(def {assign} set!)",
+        "(def {alias-set x} {list (assign {x} 2) x})",
+        "(alias-set 1)",
+        "(def {eval-set x} {list (eval {set! {x} 3}) x})",
+        "(eval-set 1)",
+        "(def {if-shadow if} {if 1 2 3})",
+        "(if-shadow (lambda {a b c} {+ a b c}))",
+        "(def {when-both a b} {when (and a b) 'y'})",
+        "(when-both #t #t)",
+        "(def {find-big l} {call/cc (lambda {k} {begin (map (lambda {x} {when (> x 3) (k x)}) l) ()})})",
+        "(find-big {1 3 4 5})",
+    ];
+
+    let mut compiled = Rlisp::new();
+    let mut evaluator = Rlisp::new();
+    evaluator.set_compile(false);
+
+    for program in programs.iter() {
+        assert_eq!((program, compiled.execute(program)), (program, evaluator.execute(program)));
+    }
+}
+
+#[test]
+fn test_register_fn() {
+    let mut rlisp = Rlisp::new();
+
+    let calls = Rc::new(RefCell::new(0));
+    let counter = calls.clone();
+
+    rlisp.register_fn("count-calls", Vec::new(), move |_: &[Value]| {
+        *counter.borrow_mut() += 1;
+        *counter.borrow()
+    }).unwrap();
+
+    assert_eq!(rlisp.execute("(count-calls)"), "1");
+    assert_eq!(rlisp.execute("(count-calls)"), "2");
+    assert_eq!(*calls.borrow(), 2);
+
+    rlisp.register_fn("scale", vec![Type::IntegerT, Type::IntegerT], |args: &[Value]| {
+        match (&args[0], &args[1]) {
+            (&Value::Integer(a), &Value::Integer(b)) => Some(a * b),
+            _                                        => None,
+        }
+    }).unwrap();
+
+    assert_eq!(rlisp.execute("(scale 3 4)"), "12");
+    assert_eq!(rlisp.execute("(scale 3 'a')"), "Error: scale, argument 2 is of type Char expected Integer");
+    assert_eq!(rlisp.execute("(scale 1 2 3)"), "Error: scale, requiers 2 arguments, got 3");
+
+    assert_eq!(rlisp.execute("(def {triple} (scale 3))"), "()");
+    assert_eq!(rlisp.execute("(triple 5)"), "15");
+    assert_eq!(rlisp.execute("triple"), "func: (scale Integer)");
+    assert_eq!(rlisp.execute("(triple)"), "Error: func: (scale Integer) got no arguments");
+    assert_eq!(rlisp.execute("(map (scale 2) {1 2 3})"), "{2 4 6}");
+    assert_eq!(rlisp.execute("(is-type scale :Builtin)"), "#t");
+
+    let e = rlisp.eval_str("(def {f x} {scale x #t}) (f 1)").unwrap_err();
+    let names = e.backtrace.iter().map(|f| f.name.clone()).collect::<Vec<Option<String>>>();
+    assert_eq!(names, vec![Some("scale".to_string()), Some("f".to_string())]);
+
+    rlisp.register_fn("checked-div", vec![Type::IntegerT, Type::IntegerT], |args: &[Value]| {
+        match (&args[0], &args[1]) {
+            (_, &Value::Integer(0))                  => Err(RlispError::new(ErrorKind::Runtime, "division by zero".to_string(), None)),
+            (&Value::Integer(a), &Value::Integer(b)) => Ok(a / b),
+            _                                        => Ok(0),
+        }
+    }).unwrap();
+
+    assert_eq!(rlisp.execute("(checked-div 6 3)"), "2");
+    assert_eq!(rlisp.execute("(try (checked-div 1 0) (catch e (error-message e)))"), "\"division by zero\"");
+
+    assert!(rlisp.register_fn("bad", vec![Type::ROptionalT(Box::new(Type::IntegerT)), Type::IntegerT],
+                              |_: &[Value]| 0).is_err());
+}
+
+#[test]
+fn test_conversions() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {pad s n} {if (== n 0) s {pad (str-concat s \".\") (- n 1)}})"), "()");
+    assert_eq!(rlisp.call::<_, _, String>("pad", ("x", 2)), Ok("x..".to_string()));
+    assert_eq!(rlisp.call::<_, _, i64>("+", (1, 2, 3)), Ok(6));
+    assert_eq!(rlisp.call::<_, _, Vec<i64>>("list", vec![1i64.into_cell(), 2i64.into_cell()]), Ok(vec![1, 2]));
+    assert_eq!(rlisp.call::<_, _, (i64, String)>("list", (1, "a")), Ok((1, "a".to_string())));
+
+    let e = rlisp.call::<_, _, i64>("undefined", ()).unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (ErrorKind::Runtime, "Undefined symbol: undefined"));
+    let e = rlisp.call::<_, _, i64>("+", (1, 'a')).unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Runtime);
+
+    assert_eq!(rlisp.execute("(def {xs} {1 2 3})"), "()");
+    assert_eq!(rlisp.get::<Vec<i64>>("xs"), Ok(vec![1, 2, 3]));
+    assert_eq!(rlisp.get::<Option<Vec<f64>>>("xs"), Ok(Some(vec![1.0, 2.0, 3.0])));
+
+    let e = rlisp.get::<Vec<String>>("xs").unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (ErrorKind::Conversion, "element 1: expected String, got Integer"));
+
+    assert_eq!(rlisp.execute("(def {m} #{a 1 b 2})"), "()");
+    let m = rlisp.get::<HashMap<String, i64>>("m").unwrap();
+    assert_eq!((m.get("a"), m.get("b")), (Some(&1), Some(&2)));
+
+    let mut scores = HashMap::new();
+    scores.insert("ann".to_string(), 3i64);
+    assert_eq!(rlisp.call::<_, _, i64>("get", (scores, "ann")), Ok(3));
+}
+
+#[test]
+fn test_globals_and_handles() {
+    let mut rlisp = Rlisp::new();
+
+    rlisp.set_global("limit", 10);
+    rlisp.set_global("names", vec!["a", "b"]);
+    assert_eq!(rlisp.execute("(+ limit 1)"), "11");
+    assert_eq!(rlisp.execute("(len names)"), "2");
+    assert_eq!(rlisp.get_global("limit"), Some(Cell::Integer(10)));
+    assert_eq!(rlisp.get_global("+"), None);
+
+    assert_eq!(rlisp.remove_global("limit"), Some(Cell::Integer(10)));
+    assert_eq!(rlisp.remove_global("limit"), None);
+    assert_eq!(rlisp.execute("limit"), "Error: Undefined symbol: limit");
+
+    // Strings passed as arguments are never parsed as code
+    assert_eq!(rlisp.execute("(def {shout s} {str-upper s})"), "()");
+    assert_eq!(rlisp.call::<_, _, String>("shout", ("\") (def {pwned} 1) (\"",)), Ok("\") (DEF {PWNED} 1) (\"".to_string()));
+    assert_eq!(rlisp.get_global("pwned"), None);
+
+    let shout = rlisp.get_global("shout").unwrap();
+    assert_eq!(rlisp.call::<_, _, String>(&shout, ("a",)), Ok("A".to_string()));
+    let names = rlisp.get_global("names").unwrap();
+    assert_eq!(rlisp.call::<_, _, i64>(names, ()).unwrap_err().message,
+               "{\"a\" \"b\"} is not a procedure, is {String String}");
+
+    let handle = rlisp.handle("shout").unwrap();
+    assert_eq!(rlisp.execute("(def {shout s} {str-lower s})"), "()");
+    assert_eq!(rlisp.call::<_, _, String>(&handle, ("b",)), Ok("B".to_string()));
+    assert_eq!(rlisp.call::<_, _, String>("shout", ("b",)), Ok("b".to_string()));
+
+    let add = rlisp.handle("+").unwrap();
+    assert_eq!(rlisp.call::<_, _, i64>(&add, (1, 2)), Ok(3));
+    assert_eq!(rlisp.call::<_, _, i64>(&add, (1, 'a')).unwrap_err().message,
+               "+, argument 2 is of type Char expected Integer|Float...");
+
+    assert_eq!(rlisp.handle("names").unwrap_err().message, "{\"a\" \"b\"} is not a procedure, is {String String}");
+    assert_eq!(rlisp.handle("missing").unwrap_err().message, "Undefined symbol: missing");
+}
+
+#[test]
+fn test_limits() {
+    let mut rlisp = Rlisp::new();
+
+    assert_eq!(rlisp.execute("(def {forever n} {forever (+ n 1)})"), "()");
+    assert_eq!(rlisp.execute("(def {deep n} {+ 1 (deep (+ n 1))})"), "()");
+    assert_eq!(rlisp.execute("(def {count n} {if (== n 0) 0 {count (- n 1)}})"), "()");
+
+    rlisp.set_limits(Limits { steps: Some(10000), timeout: None, depth: None });
+
+    let e = rlisp.eval_str("(forever 0)").unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (ErrorKind::Limit, "step limit exceeded"));
+    assert_eq!(rlisp.execute("(count 100)"), "0");
+
+    // Neither try nor call/cc can keep a sandboxed snippet running
+    assert_eq!(rlisp.execute("(try (forever 0) (catch e 0))"), "Error: step limit exceeded");
+    assert_eq!(rlisp.execute("(call/cc (lambda {k} {forever 0}))"), "Error: step limit exceeded");
+    assert_eq!(rlisp.call::<_, _, i64>("forever", (0,)).unwrap_err().kind, ErrorKind::Limit);
+
+    rlisp.set_limits(Limits { steps: None, timeout: None, depth: Some(200) });
+
+    let e = rlisp.eval_str("(deep 0)").unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (ErrorKind::Limit, "call depth limit exceeded"));
+    assert_eq!(rlisp.execute("(count 10000)"), "0");
+
+    rlisp.set_limits(Limits { steps: None, timeout: Some(Duration::from_millis(20)), depth: None });
+
+    let e = rlisp.eval_str("(forever 0)").unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (ErrorKind::Limit, "time limit exceeded"));
+    assert_eq!(rlisp.execute("(+ 1 2)"), "3");
+
+    // No depth limit by default
+    rlisp.set_limits(Limits::default());
+    assert_eq!(rlisp.execute("(def {build n acc} {if (== n 0) acc {build (- n 1) (join {1} acc)}})"), "()");
+    assert_eq!(rlisp.execute("(def {length l} {if (== l {}) 0 {+ 1 (length (tail l))}})"), "()");
+    assert_eq!(rlisp.execute("(length (build 300 {}))"), "300");
+
+    rlisp.set_limits(Limits { depth: Some(THREAD_DEPTH), ..Limits::default() });
+    assert_eq!(rlisp.execute("(count 20000)"), "0");
+    assert_eq!(rlisp.execute("(deep 0)"), "Error: call depth limit exceeded");
+}